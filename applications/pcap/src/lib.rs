@@ -0,0 +1,149 @@
+//! Controls the packet capture tap on NIC queues and dumps the captured frames,
+//! either as a one-line summary per frame or as a pcap file that can be opened in Wireshark.
+
+#![no_std]
+#[macro_use] extern crate alloc;
+#[macro_use] extern crate terminal_print;
+
+extern crate getopts;
+extern crate packet_capture;
+extern crate tsc;
+extern crate task;
+extern crate memfs;
+
+use getopts::{Matches, Options};
+use alloc::{
+    vec::Vec,
+    string::{String, ToString},
+    sync::Arc,
+};
+use packet_capture::{CapturedFrame, Direction};
+use memfs::MemFile;
+
+pub fn main(args: Vec<String>) -> isize {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    opts.optopt("c", "capacity", "maximum number of frames held before new frames are dropped (default: 1024)", "N");
+    opts.optopt("s", "snaplen", "maximum number of bytes captured from each frame (default: 1514)", "N");
+    opts.optopt("w", "write", "write the captured frames to FILE in the pcap format instead of printing them", "FILE");
+    opts.optflag("x", "hex", "also print the contents of each frame in hexadecimal");
+
+    let matches = match opts.parse(&args) {
+        Ok(m) => m,
+        Err(_f) => {
+            println!("{}", _f);
+            return print_usage(opts);
+        }
+    };
+
+    if matches.opt_present("h") {
+        return print_usage(opts);
+    }
+
+    let result = match matches.free.get(0).map(|s| s.as_str()) {
+        Some("start") => start(&matches),
+        Some("stop")  => {
+            packet_capture::stop();
+            Ok(())
+        }
+        Some("dump")  => dump(&matches),
+        _ => return print_usage(opts),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            println!("Error: {}", e);
+            -1
+        }
+    }
+}
+
+fn start(matches: &Matches) -> Result<(), &'static str> {
+    let capacity = parse_opt(matches, "c", packet_capture::DEFAULT_CAPACITY)?;
+    let snaplen = parse_opt(matches, "s", packet_capture::DEFAULT_SNAPLEN)?;
+    packet_capture::start(capacity, snaplen);
+    println!("Capturing up to {} frames ({} bytes each) on all NIC queues.", capacity, snaplen);
+    Ok(())
+}
+
+fn dump(matches: &Matches) -> Result<(), &'static str> {
+    let frames = packet_capture::drain();
+    let dropped = packet_capture::dropped_frames();
+    let first_tsc = frames.first().map(|f| f.tsc_ticks).unwrap_or(0);
+    let tsc_freq = tsc::get_tsc_frequency()?;
+    // Timestamps are relative to the first frame in this dump.
+    let timestamp_ns = |frame: &CapturedFrame| {
+        (frame.tsc_ticks.saturating_sub(first_tsc) as u128 * 1_000_000_000 / tsc_freq) as u64
+    };
+
+    if let Some(file_name) = matches.opt_str("w") {
+        let mut contents = Vec::new();
+        contents.extend_from_slice(&packet_capture::pcap_file_header(packet_capture::snaplen()));
+        for frame in &frames {
+            contents.extend_from_slice(&frame.pcap_record_header(timestamp_ns(frame)));
+            contents.extend_from_slice(&frame.data);
+        }
+
+        let taskref = task::get_my_current_task().ok_or("failed to get current task")?;
+        let curr_dir = Arc::clone(&taskref.get_env().lock().working_dir);
+        let file = MemFile::new(file_name.to_string(), &curr_dir)?;
+        file.lock().write_at(&contents, 0)?;
+        println!("Wrote {} frames to {}.", frames.len(), file_name);
+    } else {
+        for frame in &frames {
+            let ns = timestamp_ns(frame);
+            let direction = match frame.direction {
+                Direction::Receive  => "RX",
+                Direction::Transmit => "TX",
+            };
+            println!("{}.{:09} {} q{} len {}: {}", ns / 1_000_000_000, ns % 1_000_000_000, 
+                direction, frame.queue_id, frame.original_length, ethernet_summary(&frame.data));
+            if matches.opt_present("x") {
+                print_hex(&frame.data);
+            }
+        }
+    }
+
+    if dropped > 0 {
+        println!("{} frames were dropped because the capture ring was full.", dropped);
+    }
+    Ok(())
+}
+
+/// Returns a summary of the Ethernet header at the start of `data`.
+fn ethernet_summary(data: &[u8]) -> String {
+    if data.len() < 14 {
+        return "truncated Ethernet header".to_string();
+    }
+    let mac = |b: &[u8]| format!("{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", b[0], b[1], b[2], b[3], b[4], b[5]);
+    let ethertype = u16::from_be_bytes([data[12], data[13]]);
+    format!("{} > {}, ethertype {:#06x}", mac(&data[6..12]), mac(&data[0..6]), ethertype)
+}
+
+fn print_hex(data: &[u8]) {
+    for (i, line) in data.chunks(16).enumerate() {
+        let mut s = format!("    {:04x}: ", i * 16);
+        for byte in line {
+            s.push_str(&format!("{:02x} ", byte));
+        }
+        println!("{}", s);
+    }
+}
+
+fn parse_opt(matches: &Matches, opt: &str, default: usize) -> Result<usize, &'static str> {
+    match matches.opt_str(opt) {
+        Some(s) => s.parse::<usize>().map_err(|_e| "couldn't parse numeric argument"),
+        None => Ok(default),
+    }
+}
+
+fn print_usage(opts: Options) -> isize {
+    println!("{}", opts.usage(USAGE));
+    0
+}
+
+const USAGE: &'static str = "Usage: pcap start [-c CAPACITY] [-s SNAPLEN]
+       pcap stop
+       pcap dump [-x] [-w FILE]
+Captures frames received and transmitted on all NIC queues.";
@@ -0,0 +1,94 @@
+//! Application which checks the functionality of the loopback NIC by sending frames through it
+//! and verifying that the exact same bytes are received back.
+//! 
+//! It also sends more frames than the loopback NIC can hold pending,
+//! and checks that the excess frames are dropped rather than allocating more receive buffers.
+
+#![no_std]
+extern crate alloc;
+#[macro_use] extern crate terminal_print;
+extern crate loopback_nic;
+extern crate network_interface_card;
+extern crate nic_buffers;
+
+use alloc::vec::Vec;
+use alloc::string::String;
+use network_interface_card::NetworkInterfaceCard;
+use nic_buffers::TransmitBuffer;
+
+/// The length of each test frame, which is just larger than a minimum-sized Ethernet frame.
+const TEST_FRAME_LENGTH: u16 = 64;
+
+/// More frames than the loopback NIC allows to be pending at once.
+const NUM_FLOOD_FRAMES: usize = 200;
+
+pub fn main(_args: Vec<String>) -> isize {
+    match rmain() {
+        Ok(_) => {
+            println!("loopback test passed");
+            0
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            -1
+        }
+    }
+}
+
+fn rmain() -> Result<(), &'static str> {
+    let nic_ref = loopback_nic::init()?;
+    let mut nic = nic_ref.lock();
+
+    // Discard any frames left pending by other users of the loopback NIC.
+    while nic.get_received_frame().is_some() { }
+
+    // A single frame should be received with exactly the bytes that were sent.
+    nic.send_packet(create_test_frame(0xA5)?)?;
+    let frame = nic.get_received_frame().ok_or("no frame was received after sending one")?;
+    if frame.0.len() != 1 {
+        return Err("received frame was split across multiple buffers");
+    }
+    let rx_buf = &frame.0[0];
+    if rx_buf.length != TEST_FRAME_LENGTH {
+        return Err("received frame length didn't match the sent frame length");
+    }
+    let received = rx_buf.as_slice::<u8>(0, rx_buf.length as usize)?;
+    if received.iter().enumerate().any(|(i, b)| *b != test_byte(0xA5, i)) {
+        return Err("received frame contents didn't match the sent frame contents");
+    }
+    drop(frame);
+    if nic.get_received_frame().is_some() {
+        return Err("received an extra frame after sending only one");
+    }
+
+    // Flooding the NIC without receiving should drop frames instead of exhausting memory.
+    let dropped_before = nic.dropped_frames();
+    for i in 0..NUM_FLOOD_FRAMES {
+        nic.send_packet(create_test_frame(i as u8)?)?;
+    }
+    let mut received_count = 0;
+    while nic.get_received_frame().is_some() {
+        received_count += 1;
+    }
+    let dropped = nic.dropped_frames() - dropped_before;
+    println!("flood: sent {}, received {}, dropped {}", NUM_FLOOD_FRAMES, received_count, dropped);
+    if received_count + dropped != NUM_FLOOD_FRAMES || dropped == 0 {
+        return Err("loopback NIC didn't drop the frames it couldn't hold");
+    }
+
+    Ok(())
+}
+
+/// Returns the expected value of the byte at `index` in a test frame created with the given `seed`.
+fn test_byte(seed: u8, index: usize) -> u8 {
+    seed.wrapping_add(index as u8)
+}
+
+/// Creates a test frame whose contents are derived from the given `seed`.
+fn create_test_frame(seed: u8) -> Result<TransmitBuffer, &'static str> {
+    let mut tx_buf = TransmitBuffer::new(TEST_FRAME_LENGTH)?;
+    for (i, b) in tx_buf.as_slice_mut::<u8>(0, TEST_FRAME_LENGTH as usize)?.iter_mut().enumerate() {
+        *b = test_byte(seed, i);
+    }
+    Ok(tx_buf)
+}
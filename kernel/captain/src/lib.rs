@@ -38,6 +38,7 @@ extern crate acpi;
 extern crate device_manager;
 extern crate e1000;
 extern crate scheduler;
+extern crate sleep;
 #[cfg(mirror_log_to_vga)] #[macro_use] extern crate print;
 extern crate first_application;
 extern crate exceptions_full;
@@ -156,6 +157,9 @@ pub fn init(
 
     // Now that initialization is complete, we can spawn various system tasks/daemons
     // and then the first application(s).
+    spawn::new_task_builder(sleep::periodic_callback_task, ())
+        .name("periodic_callbacks".into())
+        .spawn()?;
     console::start_connection_detection()?;
     first_application::start()?;
 
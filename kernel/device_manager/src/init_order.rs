@@ -0,0 +1,171 @@
+//! Dependency-ordered driver initialization.
+//!
+//! Each driver (or group of drivers) declares the names of the other drivers
+//! it depends on, e.g., the network drivers depend on the PCI bus scan,
+//! and the PS2 drivers depend on the full logger being available.
+//! Drivers are then initialized in topological order instead of relying on
+//! a hand-maintained sequence of calls.
+//!
+//! A driver whose dependencies are not yet known, e.g., because the bus it lives on
+//! only appears after a hotplug event, is deferred rather than treated as an error.
+//! Deferred drivers are initialized as soon as the last of their dependencies
+//! is marked as initialized via [`mark_initialized()`].
+
+use alloc::{boxed::Box, vec::Vec};
+use spin::Mutex;
+
+#[cfg(test)]
+mod test;
+
+/// The function that performs a driver's initialization.
+pub type DriverInitFn = Box<dyn FnOnce() -> Result<(), &'static str> + Send>;
+
+/// A driver initialization routine along with the names of the drivers it depends on.
+pub struct DriverInit {
+    /// The unique name of this driver, which other drivers use to depend on it.
+    pub name: &'static str,
+    /// The names of the drivers that must be initialized before this one.
+    pub depends_on: &'static [&'static str],
+    /// The function that initializes this driver.
+    pub init: DriverInitFn,
+}
+impl DriverInit {
+    /// Creates a new driver initialization entry.
+    pub fn new<F>(name: &'static str, depends_on: &'static [&'static str], init: F) -> DriverInit
+        where F: FnOnce() -> Result<(), &'static str> + Send + 'static
+    {
+        DriverInit { name, depends_on, init: Box::new(init) }
+    }
+}
+
+/// The names of all drivers that have been successfully initialized.
+static INITIALIZED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Drivers whose dependencies were not all initialized when they were submitted.
+static DEFERRED: Mutex<Vec<DriverInit>> = Mutex::new(Vec::new());
+
+/// Returns `true` if the driver with the given `name` has been successfully initialized.
+pub fn is_initialized(name: &str) -> bool {
+    INITIALIZED.lock().iter().any(|n| *n == name)
+}
+
+/// Returns the names of all drivers that have been initialized, in the order they were initialized.
+pub fn initialized_drivers() -> Vec<&'static str> {
+    INITIALIZED.lock().clone()
+}
+
+/// Returns the names of all drivers that are still waiting on one or more dependencies.
+pub fn deferred_drivers() -> Vec<&'static str> {
+    DEFERRED.lock().iter().map(|d| d.name).collect()
+}
+
+/// Initializes the given set of drivers in dependency order.
+///
+/// A driver is initialized only after every driver it depends on,
+/// whether that dependency is in the given set or was initialized earlier.
+/// Drivers that depend on a name that is neither in the given set nor already initialized
+/// are deferred until that dependency is marked as initialized.
+///
+/// Returns an error if the given drivers contain a dependency cycle
+/// or if any driver's initialization function fails;
+/// the remaining drivers are not initialized in that case.
+pub fn init_in_order(drivers: Vec<DriverInit>) -> Result<(), &'static str> {
+    for driver in &drivers {
+        if is_initialized(driver.name) || drivers.iter().filter(|d| d.name == driver.name).count() > 1 {
+            error!("init_in_order(): driver {:?} was given more than once", driver.name);
+            return Err("init_in_order(): a driver was given more than once");
+        }
+    }
+
+    // Drivers that depend on something outside of this set (and not yet initialized) are deferred,
+    // along with anything in this set that transitively depends on them.
+    let mut pending = drivers;
+    loop {
+        let unsatisfiable = pending.iter().position(|d| d.depends_on.iter().any(|dep|
+            !is_initialized(dep) && !pending.iter().any(|other| other.name == *dep)
+        ));
+        match unsatisfiable {
+            Some(idx) => {
+                let driver = pending.remove(idx);
+                // A misspelled or nonexistent dependency ends up here too, in which case the driver is never initialized.
+                warn!("Deferring init of driver {:?} until all of its dependencies {:?} are initialized", driver.name, driver.depends_on);
+                DEFERRED.lock().push(driver);
+            }
+            None => break,
+        }
+    }
+
+    // Kahn's algorithm: repeatedly initialize a driver whose dependencies have all been initialized.
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|d| d.depends_on.iter().all(|dep| is_initialized(dep)));
+        match ready {
+            Some(idx) => {
+                let driver = pending.remove(idx);
+                run_driver_init(driver)?;
+            }
+            None => {
+                error!("init_in_order(): dependency cycle among drivers {:?}",
+                    pending.iter().map(|d| d.name).collect::<Vec<_>>()
+                );
+                return Err("init_in_order(): drivers have a dependency cycle");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Submits a driver to be initialized once all of its dependencies have been initialized.
+///
+/// If all of its dependencies are already initialized, the driver is initialized immediately.
+/// This is intended for drivers whose bus or controller may appear later, e.g., after a hotplug event.
+pub fn defer_init(driver: DriverInit) -> Result<(), &'static str> {
+    if driver.depends_on.iter().all(|dep| is_initialized(dep)) {
+        run_driver_init(driver)
+    } else {
+        DEFERRED.lock().push(driver);
+        Ok(())
+    }
+}
+
+/// Marks the driver with the given `name` as initialized,
+/// e.g., when a bus or controller has been brought up outside of [`init_in_order()`].
+///
+/// Any deferred drivers whose dependencies are now all satisfied are initialized.
+pub fn mark_initialized(name: &'static str) -> Result<(), &'static str> {
+    {
+        let mut initialized = INITIALIZED.lock();
+        if !initialized.contains(&name) {
+            initialized.push(name);
+        }
+    }
+    run_ready_deferred()
+}
+
+/// Initializes the given driver, records it as initialized,
+/// and then runs any deferred drivers that were waiting on it.
+fn run_driver_init(driver: DriverInit) -> Result<(), &'static str> {
+    debug!("Initializing driver {:?}", driver.name);
+    (driver.init)().map_err(|e| {
+        error!("Failed to initialize driver {:?}: {}", driver.name, e);
+        e
+    })?;
+    mark_initialized(driver.name)
+}
+
+/// Initializes every deferred driver whose dependencies have all been initialized.
+fn run_ready_deferred() -> Result<(), &'static str> {
+    loop {
+        // Take the ready driver out of the list before running it,
+        // such that its init function is free to defer other drivers.
+        let ready = {
+            let mut deferred = DEFERRED.lock();
+            deferred.iter()
+                .position(|d| d.depends_on.iter().all(|dep| is_initialized(dep)))
+                .map(|idx| deferred.remove(idx))
+        };
+        match ready {
+            Some(driver) => run_driver_init(driver)?,
+            None => return Ok(()),
+        }
+    }
+}
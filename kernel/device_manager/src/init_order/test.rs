@@ -0,0 +1,100 @@
+//! Tests for dependency-ordered driver initialization.
+//!
+//! The initialized and deferred drivers are tracked globally and tests run concurrently,
+//! so each test uses driver names that no other test uses.
+
+use super::*;
+use alloc::sync::Arc;
+
+/// The names of the drivers that a test has initialized, in order.
+type InitLog = Arc<Mutex<Vec<&'static str>>>;
+
+/// Returns a driver that records its name in `log` when it is initialized.
+fn logging_driver(log: &InitLog, name: &'static str, depends_on: &'static [&'static str]) -> DriverInit {
+    let log = log.clone();
+    DriverInit::new(name, depends_on, move || {
+        log.lock().push(name);
+        Ok(())
+    })
+}
+
+#[test]
+fn dependencies_are_initialized_first() {
+    let log = InitLog::default();
+    init_in_order(alloc::vec![
+        logging_driver(&log, "order_c", &["order_b"]),
+        logging_driver(&log, "order_b", &["order_a"]),
+        logging_driver(&log, "order_a", &[]),
+        logging_driver(&log, "order_d", &["order_a", "order_c"]),
+    ]).unwrap();
+    assert_eq!(*log.lock(), ["order_a", "order_b", "order_c", "order_d"]);
+    assert!(is_initialized("order_d"));
+}
+
+#[test]
+fn dependency_cycle_is_rejected() {
+    let log = InitLog::default();
+    let result = init_in_order(alloc::vec![
+        logging_driver(&log, "cycle_root", &[]),
+        logging_driver(&log, "cycle_a", &["cycle_root", "cycle_b"]),
+        logging_driver(&log, "cycle_b", &["cycle_a"]),
+    ]);
+    assert!(result.is_err());
+    assert_eq!(*log.lock(), ["cycle_root"]);
+    assert!(!is_initialized("cycle_a"));
+    assert!(!is_initialized("cycle_b"));
+}
+
+#[test]
+fn duplicate_driver_is_rejected() {
+    let log = InitLog::default();
+    let result = init_in_order(alloc::vec![
+        logging_driver(&log, "duplicate", &[]),
+        logging_driver(&log, "duplicate", &[]),
+    ]);
+    assert!(result.is_err());
+    assert!(log.lock().is_empty());
+}
+
+#[test]
+fn failed_init_is_not_marked_initialized() {
+    let log = InitLog::default();
+    let result = init_in_order(alloc::vec![
+        DriverInit::new("failing", &[], || Err("failing driver")),
+        logging_driver(&log, "after_failing", &["failing"]),
+    ]);
+    assert_eq!(result, Err("failing driver"));
+    assert!(!is_initialized("failing"));
+    assert!(log.lock().is_empty());
+}
+
+#[test]
+fn unknown_dependency_is_deferred_until_marked_initialized() {
+    let log = InitLog::default();
+    init_in_order(alloc::vec![
+        logging_driver(&log, "hotplug_bus_child", &["hotplug_bus"]),
+        logging_driver(&log, "hotplug_grandchild", &["hotplug_bus_child"]),
+        logging_driver(&log, "hotplug_independent", &[]),
+    ]).unwrap();
+    assert_eq!(*log.lock(), ["hotplug_independent"]);
+    assert!(deferred_drivers().contains(&"hotplug_bus_child"));
+    assert!(deferred_drivers().contains(&"hotplug_grandchild"));
+
+    mark_initialized("hotplug_bus").unwrap();
+    assert_eq!(*log.lock(), ["hotplug_independent", "hotplug_bus_child", "hotplug_grandchild"]);
+    assert!(!deferred_drivers().contains(&"hotplug_bus_child"));
+    assert!(!deferred_drivers().contains(&"hotplug_grandchild"));
+}
+
+#[test]
+fn defer_init_runs_immediately_if_dependencies_are_met() {
+    let log = InitLog::default();
+    mark_initialized("defer_ready_dep").unwrap();
+    defer_init(logging_driver(&log, "defer_ready", &["defer_ready_dep"])).unwrap();
+    assert_eq!(*log.lock(), ["defer_ready"]);
+
+    defer_init(logging_driver(&log, "defer_waiting", &["defer_waiting_dep"])).unwrap();
+    assert_eq!(*log.lock(), ["defer_ready"]);
+    mark_initialized("defer_waiting_dep").unwrap();
+    assert_eq!(*log.lock(), ["defer_ready", "defer_waiting"]);
+}
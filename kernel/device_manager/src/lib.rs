@@ -25,6 +25,11 @@ extern crate io;
 extern crate core2;
 #[macro_use] extern crate derive_more;
 extern crate mlx5;
+extern crate nic_initialization;
+extern crate task;
+extern crate ps2;
+
+pub mod init_order;
 
 use core::convert::TryFrom;
 use mpmc::Queue;
@@ -33,6 +38,7 @@ use memory::MemoryManagementInfo;
 use ethernet_smoltcp_device::EthernetNetworkInterface;
 use network_manager::add_to_network_interfaces;
 use alloc::vec::Vec;
+use init_order::DriverInit;
 use io::{ByteReaderWriterWrapper, LockableIo, ReaderWriter};
 use serial_port::{SerialPortAddress, take_serial_port_basic};
 use storage_manager::StorageDevice;
@@ -68,8 +74,23 @@ pub fn early_init(kernel_mmi: &mut MemoryManagementInfo) -> Result<(), &'static
 /// * The fully-featured system [`logger`],
 /// * PS2 [`keyboard`] and [`mouse`],
 /// * All other devices discovered on the [`pci`] bus.
+///
+/// Each of the above declares which others it depends on,
+/// and they are initialized in dependency order by [`init_order::init_in_order()`].
 pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<(), &'static str>  {
+    init_order::init_in_order(alloc::vec![
+        DriverInit::new("logger",       &[],                init_logger),
+        DriverInit::new("serial_port",  &["logger"],        init_serial_ports),
+        DriverInit::new("ps2_keyboard", &["logger"],        move || { keyboard::init(key_producer); Ok(()) }),
+        DriverInit::new("ps2_mouse",    &["logger"],        move || { mouse::init(mouse_producer); Ok(()) }),
+        DriverInit::new("pci",          &["logger"],        init_pci_bus),
+        DriverInit::new("pci_devices",  &["pci"],           init_pci_devices),
+        DriverInit::new("filesystems",  &["pci_devices"],   init_filesystems),
+    ])
+}
 
+/// Initializes the fully-featured system logger using the serial ports from early logging.
+fn init_logger() -> Result<(), &'static str> {
     let serial_ports = logger::take_early_log_writers();
     let logger_writers = IntoIterator::into_iter(serial_ports)
         .flatten()
@@ -80,7 +101,11 @@ pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<
 
     logger::init(None, logger_writers).map_err(|_e| "BUG: logger::init() failed")?;
     info!("Initialized full logger.");
+    Ok(())
+}
 
+/// Initializes the COM1 and COM2 serial ports.
+fn init_serial_ports() -> Result<(), &'static str> {
     // Ensure that both COM1 and COM2 are initialized, for logging and/or headless operation.
     // If a serial port was used for logging (as configured in [`logger::early_init()`]),
     // ignore its inputs for purposes of starting new console instances.
@@ -95,14 +120,41 @@ pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<
     init_serial_port(SerialPortAddress::COM1);
     init_serial_port(SerialPortAddress::COM2);
 
-    keyboard::init(key_producer);
-    mouse::init(mouse_producer);
+    serial_port::register_debug_action(b't', "dump all tasks", dump_tasks_debug_action)?;
+    serial_port::register_debug_action(b'r', "reboot", reboot_debug_action)?;
+    Ok(())
+}
+
+/// A serial debug action that logs the state of every task in the system.
+fn dump_tasks_debug_action(serial_port_address: SerialPortAddress) {
+    info!("Tasks (requested via {:?}):", serial_port_address);
+    // Don't hold the task list lock while logging.
+    let tasks: Vec<_> = task::TASKLIST.lock().values().cloned().collect();
+    for task in tasks {
+        info!("    {:<5} {:?}, cpu: {:?}, pinned: {:?}, {}",
+            task.id, task.runstate(), task.running_on_cpu(), task.pinned_core(), task.name,
+        );
+    }
+}
+
+/// A serial debug action that immediately reboots the machine
+/// by pulsing the CPU reset line through the PS/2 controller.
+fn reboot_debug_action(serial_port_address: SerialPortAddress) {
+    warn!("Rebooting (requested via {:?})...", serial_port_address);
+    ps2::ps2_write_command(0xFE);
+}
 
+/// Scans the PCI bus to discover all PCI devices.
+fn init_pci_bus() -> Result<(), &'static str> {
     // Initialize/scan the PCI bus to discover PCI devices
     for dev in pci::pci_device_iter() {
         debug!("Found pci device: {:X?}", dev);
-    } 
+    }
+    Ok(())
+}
 
+/// Initializes the drivers for every supported device discovered on the PCI bus.
+fn init_pci_devices() -> Result<(), &'static str> {
     // store all the initialized ixgbe NICs here to be added to the network interface list
     let mut ixgbe_devs = Vec::new();
 
@@ -134,7 +186,7 @@ pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<
         if dev.class == 0x02 && dev.subclass == 0x00 {
             if dev.vendor_id == e1000::INTEL_VEND && dev.device_id == e1000::E1000_DEV {
                 info!("e1000 PCI device found at: {:?}", dev.location);
-                let e1000_nic_ref = e1000::E1000Nic::init(dev)?;
+                let e1000_nic_ref = e1000::E1000Nic::init(dev, nic_initialization::STANDARD_MTU)?;
                 let e1000_interface = EthernetNetworkInterface::new_ipv4_interface(e1000_nic_ref, DEFAULT_LOCAL_IP, &DEFAULT_GATEWAY_IP)?;
                 add_to_network_interfaces(e1000_interface);
                 continue;
@@ -148,6 +200,7 @@ pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<
                 const RSS_ENABLED: bool = false;
                 const RX_DESCS: u16 = 8;
                 const TX_DESCS: u16 = 8;
+                const MTU: u16 = nic_initialization::STANDARD_MTU;
                 
                 let ixgbe_nic = ixgbe::IxgbeNic::init(
                     dev, 
@@ -156,6 +209,7 @@ pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<
                     None, 
                     RSS_ENABLED, 
                     ixgbe::RxBufferSizeKiB::Buffer2KiB,
+                    MTU,
                     RX_DESCS,
                     TX_DESCS
                 )?;
@@ -194,7 +248,11 @@ pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<
     if network_manager::NETWORK_INTERFACES.lock().is_empty() {
         warn!("Note: no network devices found on this system.");
     }
+    Ok(())
+}
 
+/// Discovers filesystems on the storage devices initialized from the PCI bus.
+fn init_filesystems() -> Result<(), &'static str> {
     // Discover filesystems from each storage device on the storage controllers initialized above
     // and mount each filesystem to the root directory by default.
     if false {
@@ -231,7 +289,6 @@ pub fn init(key_producer: Queue<Event>, mouse_producer: Queue<Event>) -> Result<
             }
         }
     }
-
     Ok(())
 }
 
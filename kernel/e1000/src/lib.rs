@@ -15,7 +15,7 @@ extern crate irq_safety;
 extern crate kernel_config;
 extern crate memory;
 extern crate pci; 
-extern crate owning_ref;
+extern crate hw_ring;
 extern crate interrupts;
 extern crate x86_64;
 extern crate mpmc;
@@ -24,6 +24,7 @@ extern crate intel_ethernet;
 extern crate nic_buffers;
 extern crate nic_queues;
 extern crate nic_initialization;
+extern crate mdio;
 
 pub mod test_e1000_driver;
 mod regs;
@@ -33,24 +34,25 @@ use spin::Once;
 use alloc::vec::Vec;
 use alloc::collections::VecDeque;
 use irq_safety::MutexIrqSafe;
-use alloc::boxed::Box;
-use memory::{PhysicalAddress, MappedPages};
-use pci::{PciDevice, PCI_INTERRUPT_LINE, PciConfigSpaceAccessMechanism};
+use pci::{PciDevice, PCI_INTERRUPT_LINE, PciConfigSpaceAccessMechanism, PciEnableGuard, PCI_COMMAND_MEMORY_SPACE, PCI_COMMAND_BUS_MASTER, BarRegisters};
 use kernel_config::memory::PAGE_SIZE;
-use owning_ref::BoxRefMut;
+use hw_ring::HwRing;
 use interrupts::{eoi, register_interrupt};
 use x86_64::structures::idt::InterruptStackFrame;
-use network_interface_card:: NetworkInterfaceCard;
-use nic_initialization::{allocate_memory, init_rx_buf_pool, init_rx_queue, init_tx_queue};
+use network_interface_card::{NetworkInterfaceCard, ReceiveFilter, LinkControl, LinkState, LinkSpeed, Duplex, LinkAbilities};
+use nic_initialization::{init_rx_buf_pool, init_rx_queue, init_tx_queue, max_frame_size, STANDARD_MTU};
 use intel_ethernet::descriptors::{LegacyRxDescriptor, LegacyTxDescriptor};
 use nic_buffers::{TransmitBuffer, ReceiveBuffer, ReceivedFrame};
 use nic_queues::{RxQueue, TxQueue, RxQueueRegisters, TxQueueRegisters};
+use mdio::{Phy, IntelMdic};
 
 pub const INTEL_VEND:           u16 = 0x8086;  // Vendor ID for Intel 
 pub const E1000_DEV:            u16 = 0x100E;  // Device ID for the e1000 Qemu, Bochs, and VirtualBox emmulated NICs
 
 const E1000_NUM_RX_DESC:        u16 = 8;
 const E1000_NUM_TX_DESC:        u16 = 8;
+/// When sending a batch of packets, a status write-back is requested once every this many transmit descriptors.
+const E1000_TX_RS_THRESHOLD:    u16 = 4;
 
 /// Currently, each receive buffer is a single page.
 const E1000_RX_BUFFER_SIZE_IN_BYTES:     u16 = PAGE_SIZE as u16;
@@ -83,7 +85,7 @@ lazy_static! {
 
 /// A struct which contains the receive queue registers and implements the `RxQueueRegisters` trait,
 /// which is required to store the registers in an `RxQueue` object.
-struct E1000RxQueueRegisters(BoxRefMut<MappedPages, E1000RxRegisters>);
+struct E1000RxQueueRegisters(BarRegisters<E1000RxRegisters>);
 
 impl RxQueueRegisters for E1000RxQueueRegisters {
     fn set_rdbal(&mut self, value: u32) {
@@ -105,7 +107,7 @@ impl RxQueueRegisters for E1000RxQueueRegisters {
 
 /// A struct which contains the transmit queue registers and implements the `TxQueueRegisters` trait,
 /// which is required to store the registers in a `TxQueue` object.
-struct E1000TxQueueRegisters(BoxRefMut<MappedPages, E1000TxRegisters>);
+struct E1000TxQueueRegisters(BarRegisters<E1000TxRegisters>);
 
 impl TxQueueRegisters for E1000TxQueueRegisters {
     fn set_tdbal(&mut self, value: u32) {
@@ -127,24 +129,26 @@ impl TxQueueRegisters for E1000TxQueueRegisters {
 
 /// Struct representing an e1000 network interface card.
 pub struct E1000Nic {
+    /// See [`PciEnableGuard`] for why this is the first field.
+    pci_enable_guard: PciEnableGuard,
     /// Type of BAR0
     bar_type: u8,
-    /// MMIO Base Address
-    mem_base: PhysicalAddress,
     ///interrupt number
     interrupt_num: u8,
     /// The actual MAC address burnt into the hardware of this E1000 NIC.
     mac_hardware: [u8; 6],
     /// The optional spoofed MAC address to use in place of `mac_hardware` when transmitting.  
     mac_spoofed: Option<[u8; 6]>,
+    /// The largest frame payload that the NIC is configured to receive
+    mtu: u16,
     /// Receive queue with descriptors
     rx_queue: RxQueue<E1000RxQueueRegisters,LegacyRxDescriptor>,
     /// Transmit queue with descriptors
     tx_queue: TxQueue<E1000TxQueueRegisters,LegacyTxDescriptor>,     
     /// memory-mapped control registers
-    regs: BoxRefMut<MappedPages, E1000Registers>,
+    regs: BarRegisters<E1000Registers>,
     /// memory-mapped registers holding the MAC address
-    mac_regs: BoxRefMut<MappedPages, E1000MacRegisters>
+    mac_regs: BarRegisters<E1000MacRegisters>
 }
 
 
@@ -166,6 +170,103 @@ impl NetworkInterfaceCard for E1000Nic {
     fn mac_address(&self) -> [u8; 6] {
         self.mac_spoofed.unwrap_or(self.mac_hardware)
     }
+
+    fn mtu(&self) -> u16 {
+        self.mtu
+    }
+}
+
+
+
+impl LinkControl for E1000Nic {
+
+    fn link_state(&self) -> LinkState {
+        let status = self.regs.status.read();
+        let up = status & STATUS_LU == STATUS_LU;
+        let speed = match (status & STATUS_SPEED_MASK) >> STATUS_SPEED_SHIFT {
+            0 => LinkSpeed::Mbps10,
+            1 => LinkSpeed::Mbps100,
+            _ => LinkSpeed::Mbps1000,
+        };
+        let duplex = if status & STATUS_FD == STATUS_FD { Duplex::Full } else { Duplex::Half };
+        LinkState {
+            up,
+            speed: if up { Some(speed) } else { None },
+            duplex: if up { Some(duplex) } else { None },
+        }
+    }
+
+    fn force_link(&mut self, speed: LinkSpeed, duplex: Duplex) -> Result<(), &'static str> {
+        // 1000BASE-T requires auto-negotiation, so only 10 and 100 Mbps links can be forced.
+        let ctrl_speed = match speed {
+            LinkSpeed::Mbps10  => CTRL_SPEED_10,
+            LinkSpeed::Mbps100 => CTRL_SPEED_100,
+            _ => return Err("e1000: only 10 and 100 Mbps links can be forced, higher speeds require auto-negotiation"),
+        };
+        let ctrl_duplex = match duplex {
+            Duplex::Full => CTRL_FD,
+            Duplex::Half => 0,
+        };
+
+        // configure the PHY first, then force the MAC to match it
+        self.phy().force(speed, duplex)?;
+
+        let ctrl = self.regs.ctrl.read() & !(CTRL_SPEED_MASK | CTRL_FD | CTRL_ASDE);
+        self.regs.ctrl.write(ctrl | CTRL_SLU | CTRL_FRCSPD | CTRL_FRCDPLX | ctrl_speed | ctrl_duplex);
+        Ok(())
+    }
+
+    fn restart_autonegotiation(&mut self) -> Result<(), &'static str> {
+        // let the MAC take its speed and duplex settings from the PHY again
+        let ctrl = self.regs.ctrl.read() & !(CTRL_FRCSPD | CTRL_FRCDPLX);
+        self.regs.ctrl.write(ctrl | CTRL_SLU | CTRL_ASDE);
+
+        self.phy().restart_autonegotiation()
+    }
+
+    fn link_partner_abilities(&mut self) -> Result<LinkAbilities, &'static str> {
+        self.phy().link_partner_abilities()
+    }
+}
+
+
+
+impl ReceiveFilter for E1000Nic {
+
+    fn set_unicast_address(&mut self, mac_address: [u8; 6]) -> Result<(), &'static str> {
+        let low = u32::from_le_bytes([mac_address[0], mac_address[1], mac_address[2], mac_address[3]]);
+        let high = u32::from_le_bytes([mac_address[4], mac_address[5], 0, 0]);
+        // clear the Address Valid bit while the address is being changed
+        self.mac_regs.rah.write(0);
+        self.mac_regs.ral.write(low);
+        self.mac_regs.rah.write(high | RAH_AV);
+
+        self.mac_spoofed = if mac_address == self.mac_hardware { None } else { Some(mac_address) };
+        Ok(())
+    }
+
+    fn set_multicast_addresses(&mut self, multicast_addresses: &[[u8; 6]]) -> Result<(), &'static str> {
+        let mut mta = [0u32; E1000_NUM_MTA_REGS];
+        for addr in multicast_addresses {
+            let hash = Self::multicast_hash(addr);
+            mta[hash >> 5] |= 1 << (hash & 0x1F);
+        }
+        for (reg, value) in self.mac_regs.mta.iter_mut().zip(mta.iter()) {
+            reg.write(*value);
+        }
+        Ok(())
+    }
+
+    fn set_promiscuous(&mut self, enable: bool) -> Result<(), &'static str> {
+        let rctl = self.regs.rctl.read();
+        let rctl = if enable {
+            rctl | RCTL_UPE | RCTL_MPE
+        } else {
+            rctl & !(RCTL_UPE | RCTL_MPE)
+        };
+        self.regs.rctl.write(rctl);
+        Ok(())
+    }
 }
 
 
@@ -173,9 +274,15 @@ impl NetworkInterfaceCard for E1000Nic {
 /// Functions that setup the NIC struct and handle the sending and receiving of packets.
 impl E1000Nic {
     /// Initializes the new E1000 network interface card that is connected as the given PciDevice.
-    pub fn init(e1000_pci_dev: &PciDevice) -> Result<&'static MutexIrqSafe<E1000Nic>, &'static str> {
+    /// 
+    /// If `mtu` is larger than [`STANDARD_MTU`], long packet reception is enabled,
+    /// and jumbo frames span multiple receive buffers. 
+    pub fn init(e1000_pci_dev: &PciDevice, mtu: u16) -> Result<&'static MutexIrqSafe<E1000Nic>, &'static str> {
         use interrupts::IRQ_BASE_OFFSET;
 
+        // ensure the MTU is supported; with long packet reception enabled, the 82540EM accepts frames of up to 16 KiB.
+        max_frame_size(mtu)?;
+
         //debug!("e1000_nc bar_type: {0}, mem_base: {1}, io_base: {2}", e1000_nc.bar_type, e1000_nc.mem_base, e1000_nc.io_base);
         
         // Get interrupt number
@@ -192,20 +299,20 @@ impl E1000Nic {
             return Err("e1000::init(): BAR0 is of I/O type")
         }
   
-        // memory mapped base address
-        let mem_base = e1000_pci_dev.determine_mem_base(0)?;
-
-        // set the bus mastering bit for this PciDevice, which allows it to use DMA
-        e1000_pci_dev.pci_set_command_bus_master_bit();
+        // set the memory space and bus mastering bits for this PciDevice, which allows it to use MMIO and DMA
+        let pci_enable_guard = e1000_pci_dev.pci_enable(PCI_COMMAND_MEMORY_SPACE | PCI_COMMAND_BUS_MASTER, "e1000");
 
-        let (mut mapped_registers, rx_registers, tx_registers, mut mac_registers)  = Self::map_e1000_regs(e1000_pci_dev, mem_base)?;
+        let (mut mapped_registers, rx_registers, tx_registers, mut mac_registers)  = Self::map_e1000_regs(e1000_pci_dev)?;
         let mut rx_registers =  E1000RxQueueRegisters(rx_registers);
         let mut tx_registers =  E1000TxQueueRegisters(tx_registers);
 
         Self::start_link(&mut mapped_registers);
         
         let mac_addr_hardware = Self::read_mac_address_from_nic(&mut mac_registers);
-        //e1000_nc.clear_multicast();
+        // the multicast table's contents are undefined after reset, so start out with an empty table
+        for mta in mac_registers.mta.iter_mut() {
+            mta.write(0);
+        }
         //e1000_nc.clear_statistics();
         
         Self::enable_interrupts(&mut mapped_registers);
@@ -217,12 +324,11 @@ impl E1000Nic {
         // initialize the buffer pool
         init_rx_buf_pool(RX_BUFFER_POOL_SIZE, E1000_RX_BUFFER_SIZE_IN_BYTES, &RX_BUFFER_POOL)?;
 
-        let (rx_descs, rx_buffers) = Self::rx_init(&mut mapped_registers, &mut rx_registers)?;
+        let (rx_descs, rx_buffers) = Self::rx_init(&mut mapped_registers, &mut rx_registers, mtu)?;
         let rxq = RxQueue {
             id: 0,
             regs: rx_registers,
             rx_descs: rx_descs,
-            num_rx_descs: E1000_NUM_RX_DESC,
             rx_cur: 0,
             rx_bufs_in_use: rx_buffers,
             rx_buffer_size_bytes: E1000_RX_BUFFER_SIZE_IN_BYTES,
@@ -238,17 +344,19 @@ impl E1000Nic {
             id: 0,
             regs: tx_registers,
             tx_descs: tx_descs,
-            num_tx_descs: E1000_NUM_TX_DESC,
             tx_cur: 0,
             cpu_id: None,
+            tx_bufs_in_flight: VecDeque::new(),
+            rs_threshold: E1000_TX_RS_THRESHOLD,
         };
 
         let e1000_nic = E1000Nic {
+            pci_enable_guard,
             bar_type: bar_type,
-            mem_base: mem_base,
             interrupt_num: interrupt_num,
             mac_hardware: mac_addr_hardware,
             mac_spoofed: None,
+            mtu,
             rx_queue: rxq,
             tx_queue: txq,
             regs: mapped_registers,
@@ -259,36 +367,29 @@ impl E1000Nic {
         Ok(nic_ref)
     }
     
-    /// Allocates memory for the NIC and maps the E1000 Register struct to that memory area.
-    /// Returns a reference to the E1000 Registers, tied to their backing `MappedPages`.
+    /// Maps the E1000 Register structs to the NIC's memory-mapped BAR0.
+    /// Returns the E1000 Registers, tied to the backing `MappedPages` of that BAR.
     /// 
     /// # Arguments
     /// * `device`: reference to the nic device
-    /// * `mem_base`: the physical address where the NIC's memory starts.
     fn map_e1000_regs(
-        _device: &PciDevice, 
-        mem_base: PhysicalAddress
+        device: &PciDevice, 
     ) -> Result<(
-        BoxRefMut<MappedPages, E1000Registers>, 
-        BoxRefMut<MappedPages, E1000RxRegisters>, 
-        BoxRefMut<MappedPages, E1000TxRegisters>, 
-        BoxRefMut<MappedPages, E1000MacRegisters>
+        BarRegisters<E1000Registers>, 
+        BarRegisters<E1000RxRegisters>, 
+        BarRegisters<E1000TxRegisters>, 
+        BarRegisters<E1000MacRegisters>
     ), &'static str> {
 
         const GENERAL_REGISTERS_SIZE_BYTES: usize = 8192;
         const RX_REGISTERS_SIZE_BYTES: usize = 4096;
         const TX_REGISTERS_SIZE_BYTES: usize = 4096;
-        const MAC_REGISTERS_SIZE_BYTES: usize = 114_688;
 
-        let nic_regs_mapped_page = allocate_memory(mem_base, GENERAL_REGISTERS_SIZE_BYTES)?;
-        let nic_rx_regs_mapped_page = allocate_memory(mem_base + GENERAL_REGISTERS_SIZE_BYTES, RX_REGISTERS_SIZE_BYTES)?;
-        let nic_tx_regs_mapped_page = allocate_memory(mem_base + GENERAL_REGISTERS_SIZE_BYTES + RX_REGISTERS_SIZE_BYTES, TX_REGISTERS_SIZE_BYTES)?;
-        let nic_mac_regs_mapped_page = allocate_memory(mem_base + GENERAL_REGISTERS_SIZE_BYTES + RX_REGISTERS_SIZE_BYTES + TX_REGISTERS_SIZE_BYTES, MAC_REGISTERS_SIZE_BYTES)?;
-
-        let regs = BoxRefMut::new(Box::new(nic_regs_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<E1000Registers>(0))?;
-        let rx_regs = BoxRefMut::new(Box::new(nic_rx_regs_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<E1000RxRegisters>(0))?;
-        let tx_regs = BoxRefMut::new(Box::new(nic_tx_regs_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<E1000TxRegisters>(0))?;
-        let mac_regs = BoxRefMut::new(Box::new(nic_mac_regs_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<E1000MacRegisters>(0))?;
+        // All of the registers are in BAR0, one region directly after another.
+        let regs = device.pci_bar_registers::<E1000Registers>(0, 0)?;
+        let rx_regs = device.pci_bar_registers::<E1000RxRegisters>(0, GENERAL_REGISTERS_SIZE_BYTES)?;
+        let tx_regs = device.pci_bar_registers::<E1000TxRegisters>(0, GENERAL_REGISTERS_SIZE_BYTES + RX_REGISTERS_SIZE_BYTES)?;
+        let mac_regs = device.pci_bar_registers::<E1000MacRegisters>(0, GENERAL_REGISTERS_SIZE_BYTES + RX_REGISTERS_SIZE_BYTES + TX_REGISTERS_SIZE_BYTES)?;
 
         Ok((regs, rx_regs, tx_regs, mac_regs))
     }
@@ -325,17 +426,21 @@ impl E1000Nic {
         debug!("e1000::start_link(): REG_CTRL: {:#X}", regs.ctrl.read());
     }
 
-    ///TODO: change to mapped pages, add reg to struct
-    /// clear multicast registers
-    /* pub fn clear_multicast (&self) {
-        for i in 0..128{
-        self.write_command(REG_MTA + (i * 4), 0);
-        }
+    /// Returns a handle to this NIC's internal PHY, accessed through the MDI control register.
+    fn phy(&mut self) -> Phy<IntelMdic<'_>> {
+        Phy::new(IntelMdic(&mut self.regs.mdic), E1000_PHY_ADDRESS)
+    }
+
+    /// Returns the index of the bit in the Multicast Table Array that corresponds to the given multicast address.
+    /// 
+    /// This uses the hash function selected by `RCTL_MO_36`, i.e., bits 47:36 of the address.
+    fn multicast_hash(mac_address: &[u8; 6]) -> usize {
+        ((mac_address[4] as usize >> 4) | ((mac_address[5] as usize) << 4)) & 0xFFF
     }
 
     ///TODO: change to mapped pages, add reg to struct
     /// clear statistic registers
-    pub fn clear_statistics (&self) {
+    /* pub fn clear_statistics (&self) {
         for i in 0..64{
         self.write_command(REG_CRCERRS + (i * 4), 0);
         }
@@ -345,9 +450,10 @@ impl E1000Nic {
     /// and returns a tuple including both of them.
     fn rx_init(
         regs: &mut E1000Registers, 
-        rx_regs: &mut E1000RxQueueRegisters
+        rx_regs: &mut E1000RxQueueRegisters,
+        mtu: u16
     ) -> Result<(
-        BoxRefMut<MappedPages, [LegacyRxDescriptor]>, 
+        HwRing<LegacyRxDescriptor>, 
         Vec<ReceiveBuffer>
     ), &'static str> {
         // get the queue of rx descriptors and its corresponding rx buffers     
@@ -362,7 +468,11 @@ impl E1000Nic {
         // This doesn't prevent all of the rx buffers from being used, they will still all be used fully.
         rx_regs.set_rdt((E1000_NUM_RX_DESC - 1) as u32); 
         // TODO: document these various e1000 flags and why we're setting them
-        regs.rctl.write(regs::RCTL_EN| regs::RCTL_SBP | regs::RCTL_LBM_NONE | regs::RTCL_RDMTS_HALF | regs::RCTL_BAM | regs::RCTL_SECRC  | regs::RCTL_BSIZE_2048);
+        // The buffer size must match the size of the receive buffers given to the NIC above, 
+        // and long packet reception (LPE) allows jumbo frames, which span multiple receive buffers.
+        let rctl = regs::RCTL_EN| regs::RCTL_SBP | regs::RCTL_LBM_NONE | regs::RTCL_RDMTS_HALF | regs::RCTL_BAM | regs::RCTL_SECRC | regs::RCTL_BSIZE_4096;
+        let rctl = if mtu > STANDARD_MTU { rctl | regs::RCTL_LPE } else { rctl };
+        regs.rctl.write(rctl);
 
         Ok((rx_descs, rx_bufs_in_use))
     }           
@@ -371,7 +481,7 @@ impl E1000Nic {
     fn tx_init(
         regs: &mut E1000Registers, 
         tx_regs: &mut E1000TxQueueRegisters
-    ) -> Result<BoxRefMut<MappedPages, [LegacyTxDescriptor]>, &'static str> {
+    ) -> Result<HwRing<LegacyTxDescriptor>, &'static str> {
         // get the queue of tx descriptors     
         let tx_descs = init_tx_queue(E1000_NUM_TX_DESC as usize, tx_regs)?;
         regs.tctl.write(regs::TCTL_EN | regs::TCTL_PSP);
@@ -22,7 +22,11 @@ pub struct E1000Registers {
     pub ctrl:                       Volatile<u32>,          // 0x0
     _padding0:                      [u8; 4],                // 0x4 - 0x7
     pub status:                     ReadOnly<u32>,          // 0x8
-    _padding1:                      [u8; 180],              // 0xC - 0xBF,  180 bytes
+    _padding1:                      [u8; 20],               // 0xC - 0x1F
+
+    /// MDI control register, used to access the PHY's registers
+    pub mdic:                       Volatile<u32>,          // 0x20
+    _padding1a:                     [u8; 156],              // 0x24 - 0xBF
     
     /// Interrupt control registers
     pub icr:                        ReadOnly<u32>,          // 0xC0   
@@ -72,7 +76,10 @@ const_assert_eq!(core::mem::size_of::<E1000TxRegisters>(), 4096);
 #[derive(FromBytes)]
 #[repr(C)]
 pub struct E1000MacRegisters {
-    _padding10:                     [u8; 5120],             // 0x4000 - 0x53FF
+    _padding10:                     [u8; 4608],             // 0x4000 - 0x51FF
+
+    /// The Multicast Table Array, a 4096-bit hash table of the multicast addresses that are accepted.
+    pub mta:                        [Volatile<u32>; 128],   // 0x5200 - 0x53FF
     
     /// The lower (least significant) 32 bits of the NIC's MAC hardware address.
     pub ral:                        Volatile<u32>,          // 0x5400
@@ -164,11 +171,44 @@ pub const REG_TIPG:                 u32 = 0x0410;
 pub const ECTRL_SLU:                u32 = 0x40;        
 
 // CTRL commands
+/// Full-Duplex
+pub const CTRL_FD:                  u32 = 1 << 0;
 pub const CTRL_LRST:                u32 = 1 << 3;
+/// Auto-Speed Detection Enable
+pub const CTRL_ASDE:                u32 = 1 << 5;
+/// Set Link Up
+pub const CTRL_SLU:                 u32 = 1 << 6;
 pub const CTRL_ILOS:                u32 = 1 << 7;
+/// Speed selection, used only when the speed is forced
+pub const CTRL_SPEED_MASK:          u32 = 3 << 8;
+pub const CTRL_SPEED_10:            u32 = 0 << 8;
+pub const CTRL_SPEED_100:           u32 = 1 << 8;
+pub const CTRL_SPEED_1000:          u32 = 2 << 8;
+/// Force Speed
+pub const CTRL_FRCSPD:              u32 = 1 << 11;
+/// Force Duplex
+pub const CTRL_FRCDPLX:             u32 = 1 << 12;
 pub const CTRL_VME:                 u32 = 1 << 30; 
 pub const CTRL_PHY_RST:             u32 = 1 << 31;
 
+// STATUS bits
+/// Link is Full-Duplex
+pub const STATUS_FD:                u32 = 1 << 0;
+/// Link Up
+pub const STATUS_LU:                u32 = 1 << 1;
+/// Link speed: 00 = 10 Mbps, 01 = 100 Mbps, 10/11 = 1000 Mbps
+pub const STATUS_SPEED_SHIFT:       u32 = 6;
+pub const STATUS_SPEED_MASK:        u32 = 3 << STATUS_SPEED_SHIFT;
+
+/// Address Valid bit in the RAH register
+pub const RAH_AV:                   u32 = 1 << 31;
+
+/// The number of 32-bit registers in the Multicast Table Array
+pub const E1000_NUM_MTA_REGS:       usize = 128;
+
+/// The address of the internal PHY on the MDI bus
+pub const E1000_PHY_ADDRESS:        u8 = 1;
+
 // RCTL commands
 /// Receiver Enable
 pub const RCTL_EN:                  u32 = 1 << 1;    
@@ -15,29 +15,31 @@ extern crate network_manager;
 use alloc::{
     boxed::Box,
     collections::BTreeMap,
+    vec::Vec,
 };
 use irq_safety::MutexIrqSafe;
 use smoltcp::{
     socket::SocketSet,
     time::Instant,
     phy::DeviceCapabilities,
-    wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address},
+    wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv6Address},
     iface::{EthernetInterface, EthernetInterfaceBuilder, NeighborCache, Routes},
 };
-use network_interface_card::NetworkInterfaceCard;
+use network_interface_card::{
+    NetworkInterfaceCard, ReceiveFilter,
+    ipv4_multicast_mac_address, ipv6_multicast_mac_address, ipv6_solicited_node_mac_address,
+};
 use nic_buffers::{TransmitBuffer, ReceivedFrame};
 use owning_ref::BoxRefMut;
 use network_manager::NetworkInterface;
 use core::str::FromStr;
 
-/// standard MTU for ethernet cards
-const DEFAULT_MTU: usize = 1500;
-
-
 /// A struct that implements the `NetworkInterface` trait for a NIC. 
 /// There should be one instance of this struct per interface, i.e., an Ethernet port on the NIC.
 pub struct EthernetNetworkInterface<N: NetworkInterfaceCard + 'static> {
     pub iface: EthernetInterface<'static, 'static, 'static, EthernetDevice<N>>,
+    /// The NIC underlying this interface, used to reprogram its receive filters.
+    nic_ref: &'static MutexIrqSafe<N>,
 }
 
 impl<N: NetworkInterfaceCard + 'static> NetworkInterface for EthernetNetworkInterface<N> { 
@@ -115,7 +117,7 @@ impl<N: NetworkInterfaceCard + 'static > EthernetNetworkInterface<N> {
             .finalize();
 
         Ok(
-            EthernetNetworkInterface { iface }
+            EthernetNetworkInterface { iface, nic_ref: nic }
         )
     }
 
@@ -168,7 +170,7 @@ impl<'d, N: NetworkInterfaceCard + 'static> smoltcp::phy::Device<'d> for Etherne
 
     fn capabilities(&self) -> DeviceCapabilities {
         let mut caps = DeviceCapabilities::default();
-        caps.max_transmission_unit = DEFAULT_MTU;
+        caps.max_transmission_unit = self.nic_ref.lock().mtu() as usize;
         caps
     }
 
@@ -187,24 +189,34 @@ impl<'d, N: NetworkInterfaceCard + 'static> smoltcp::phy::Device<'d> for Etherne
         };
 
         // debug!("EthernetDevice::receive(): got Ethernet frame, consists of {} ReceiveBuffers.", received_frame.0.len());
-        // TODO FIXME: add support for handling a frame that consists of multiple ReceiveBuffers
-        if received_frame.0.len() > 1 {
-            error!("EthernetDevice::receive(): WARNING: Ethernet frame consists of {} ReceiveBuffers, we currently only handle a single-buffer frame, so this may not work correctly!",  received_frame.0.len());
-        }
-
-        let first_buf_len = received_frame.0[0].length;
-        let rxbuf_byte_slice = BoxRefMut::new(Box::new(received_frame))
-            .try_map_mut(|rxframe| rxframe.0[0].as_slice_mut::<u8>(0, first_buf_len as usize))
-            .map_err(|e| {
-                error!("EthernetDevice::receive(): couldn't convert receive buffer of length {} into byte slice, error {:?}", first_buf_len, e);
-                e
-            })
-            .ok()?;
+        let rx_token = if received_frame.0.len() > 1 {
+            // A frame that spans multiple ReceiveBuffers (e.g., a jumbo frame) must be copied
+            // into one contiguous buffer, because smoltcp expects the frame as a single slice.
+            let mut frame_bytes = Vec::with_capacity(received_frame.0.iter().map(|b| b.length as usize).sum());
+            for rxbuf in received_frame.0.iter() {
+                let bytes = rxbuf.as_slice::<u8>(0, rxbuf.length as usize).map_err(|e| {
+                    error!("EthernetDevice::receive(): couldn't convert receive buffer of length {} into byte slice, error {:?}", rxbuf.length, e);
+                    e
+                }).ok()?;
+                frame_bytes.extend_from_slice(bytes);
+            }
+            RxToken::MultiBuffer(frame_bytes)
+        } else {
+            let first_buf_len = received_frame.0[0].length;
+            let rxbuf_byte_slice = BoxRefMut::new(Box::new(received_frame))
+                .try_map_mut(|rxframe| rxframe.0[0].as_slice_mut::<u8>(0, first_buf_len as usize))
+                .map_err(|e| {
+                    error!("EthernetDevice::receive(): couldn't convert receive buffer of length {} into byte slice, error {:?}", first_buf_len, e);
+                    e
+                })
+                .ok()?;
+            RxToken::SingleBuffer(rxbuf_byte_slice)
+        };
 
         // Just create and return a pair of (receive token, transmit token), 
         // the actual rx buffer handling is done in the RxToken::consume() function
         Some((
-            RxToken(rxbuf_byte_slice),
+            rx_token,
             TxToken {
                 nic_ref: self.nic_ref,
             },
@@ -267,13 +279,66 @@ impl<N: NetworkInterfaceCard + 'static> smoltcp::phy::TxToken for TxToken<N> {
 
 
 /// The receive token type used by smoltcp, 
-/// which contains only a `ReceivedFrame` to be consumed later.
-pub struct RxToken(BoxRefMut<ReceivedFrame, [u8]>);
+/// which contains only a received frame to be consumed later.
+pub enum RxToken {
+    /// A `ReceivedFrame` contained in a single `ReceiveBuffer`, which is given to smoltcp without copying.
+    SingleBuffer(BoxRefMut<ReceivedFrame, [u8]>),
+    /// A frame that spanned multiple `ReceiveBuffer`s, copied into one contiguous buffer.
+    MultiBuffer(Vec<u8>),
+}
 
 impl smoltcp::phy::RxToken for RxToken {
-    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    fn consume<R, F>(self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
         where F: FnOnce(&mut [u8]) -> smoltcp::Result<R>
     {
-        f(self.0.as_mut())
+        match self {
+            RxToken::SingleBuffer(mut rxbuf_byte_slice) => f(rxbuf_byte_slice.as_mut()),
+            RxToken::MultiBuffer(mut frame_bytes) => f(&mut frame_bytes),
+        }
+    }
+}
+
+impl<N: NetworkInterfaceCard + ReceiveFilter + 'static> EthernetNetworkInterface<N> {
+    /// Replaces the IP addresses assigned to this interface, e.g., when a DHCP lease is obtained or renewed,
+    /// and then reprograms the NIC's receive filters accordingly. 
+    /// 
+    /// See [`update_receive_filters()`](#method.update_receive_filters) for a description of `multicast_groups`.
+    pub fn set_ip_addrs(&mut self, ip_addrs: Vec<IpCidr>, multicast_groups: &[IpAddress]) -> Result<(), &'static str> {
+        self.iface.update_ip_addrs(|addrs| *addrs = ip_addrs.into());
+        self.update_receive_filters(multicast_groups)
     }
-}
\ No newline at end of file
+
+    /// Programs the NIC's receive filters to accept only the frames this interface needs,
+    /// and then disables promiscuous mode on the NIC.
+    /// 
+    /// The NIC will accept frames sent to this interface's Ethernet address,
+    /// frames sent to the IPv6 all-nodes and solicited-node multicast groups of its IPv6 addresses (for neighbor discovery),
+    /// and frames sent to any of the given additional IPv4 or IPv6 `multicast_groups`, e.g., `224.0.0.251` for mDNS.
+    /// 
+    /// This should be called again whenever this interface's Ethernet or IP addresses change.
+    pub fn update_receive_filters(&mut self, multicast_groups: &[IpAddress]) -> Result<(), &'static str> {
+        let mut multicast_addresses = Vec::new();
+        let mut has_ipv6_addr = false;
+        for ip_cidr in self.iface.ip_addrs() {
+            if let IpAddress::Ipv6(addr) = ip_cidr.address() {
+                has_ipv6_addr = true;
+                multicast_addresses.push(ipv6_solicited_node_mac_address(addr.0));
+            }
+        }
+        if has_ipv6_addr {
+            multicast_addresses.push(ipv6_multicast_mac_address(Ipv6Address::LINK_LOCAL_ALL_NODES.0));
+        }
+        for group in multicast_groups {
+            match group {
+                IpAddress::Ipv4(addr) if addr.is_multicast() => multicast_addresses.push(ipv4_multicast_mac_address(addr.0)),
+                IpAddress::Ipv6(addr) if addr.is_multicast() => multicast_addresses.push(ipv6_multicast_mac_address(addr.0)),
+                _ => return Err("update_receive_filters(): multicast_groups must only contain IPv4 or IPv6 multicast addresses"),
+            }
+        }
+
+        let mut nic = self.nic_ref.lock();
+        nic.set_unicast_address(self.iface.ethernet_addr().0)?;
+        nic.set_multicast_addresses(&multicast_addresses)?;
+        nic.set_promiscuous(false)
+    }
+}
@@ -0,0 +1,170 @@
+//! A ring of entries in DMA-visible memory that is shared between software and a hardware device.
+//!
+//! Many devices communicate with their driver through a circular array of descriptors
+//! that resides in physically-contiguous memory, e.g., NIC receive and transmit rings,
+//! host controller schedule lists, and virtqueues.
+//! Software and hardware each advance their own index (head or tail) around the ring,
+//! wrapping back to the first entry after the last one.
+//!
+//! [`HwRing`] handles allocating and zeroing the ring memory, index wrapping,
+//! volatile accesses to individual entries, and the memory barriers needed when
+//! handing entries to the device or taking them back from it,
+//! such that each driver doesn't need to reimplement those parts.
+
+#![no_std]
+
+extern crate alloc;
+extern crate memory;
+extern crate owning_ref;
+extern crate zerocopy;
+
+#[cfg(test)]
+mod test;
+
+use core::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{fence, Ordering},
+};
+use alloc::boxed::Box;
+use memory::{create_contiguous_mapping, EntryFlags, MappedPages, PhysicalAddress};
+use owning_ref::BoxRefMut;
+use zerocopy::FromBytes;
+
+/// A ring of `T` entries in physically-contiguous memory that is shared with a hardware device.
+///
+/// The ring dereferences to a slice of its entries for direct access,
+/// e.g., for entry types that already wrap their fields in `Volatile` types.
+/// Otherwise, [`read_volatile()`](HwRing::read_volatile) and
+/// [`write_volatile()`](HwRing::write_volatile) should be used.
+///
+/// Either way, a driver should check whether the device is done with an entry via [`is_done()`](HwRing::is_done)
+/// and hand new entries to the device via [`publish()`](HwRing::publish),
+/// which issue the barriers that order entry accesses against the device's accesses.
+pub struct HwRing<T: FromBytes> {
+    entries: BoxRefMut<MappedPages, [T]>,
+    phys_addr: PhysicalAddress,
+}
+
+impl<T: FromBytes> HwRing<T> {
+    /// Allocates a new ring with `num_entries` entries, all of which are zeroed.
+    ///
+    /// The ring begins on a page boundary, which satisfies the alignment requirements
+    /// of every descriptor ring that we know of (e.g., 128 bytes for Intel NICs).
+    ///
+    /// # Arguments
+    /// * `num_entries`: the number of entries in the ring; must be non-zero.
+    /// * `flags`: the page table flags used to map the ring, which should typically disable caching.
+    pub fn new(num_entries: usize, flags: EntryFlags) -> Result<HwRing<T>, &'static str> {
+        if num_entries == 0 {
+            return Err("HwRing::new(): a ring must have at least one entry");
+        }
+        let size_in_bytes = num_entries * core::mem::size_of::<T>();
+        let (mut mapped_pages, phys_addr) = create_contiguous_mapping(size_in_bytes, flags)?;
+
+        // All-zero bytes are a valid `T` because it implements `FromBytes`.
+        mapped_pages.as_slice_mut::<u8>(0, size_in_bytes)?.fill(0);
+
+        let entries = BoxRefMut::new(Box::new(mapped_pages))
+            .try_map_mut(|mp| mp.as_slice_mut::<T>(0, num_entries))?;
+        Ok(HwRing { entries, phys_addr })
+    }
+
+    /// Returns the number of entries in this ring.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if this ring has no entries, which is never the case for a ring created by [`HwRing::new()`].
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the starting physical address of this ring, which is given to the device.
+    pub fn phys_addr(&self) -> PhysicalAddress {
+        self.phys_addr
+    }
+
+    /// Returns the total size in bytes of all entries in this ring, which is given to the device.
+    pub fn size_in_bytes(&self) -> usize {
+        self.len() * core::mem::size_of::<T>()
+    }
+
+    /// Returns the index of the entry that follows the entry at `index`, wrapping around the end of the ring.
+    pub fn next_index(&self, index: usize) -> usize {
+        wrapping_add(index, 1, self.len())
+    }
+
+    /// Returns the index that is `count` entries after `index`, wrapping around the end of the ring.
+    pub fn wrapping_add(&self, index: usize, count: usize) -> usize {
+        wrapping_add(index, count, self.len())
+    }
+
+    /// Returns the number of entries between `from` (inclusive) and `to` (exclusive),
+    /// moving forward from `from` and wrapping around the end of the ring.
+    ///
+    /// For example, this is the number of entries that a device has yet to process
+    /// if `from` is the device's head index and `to` is the driver's tail index.
+    pub fn distance(&self, from: usize, to: usize) -> usize {
+        distance(from, to, self.len())
+    }
+
+    /// Reads the entry at `index` using a volatile read.
+    pub fn read_volatile(&self, index: usize) -> T where T: Copy {
+        let entry: *const T = &self.entries[index];
+        unsafe { core::ptr::read_volatile(entry) }
+    }
+
+    /// Writes `value` to the entry at `index` using a volatile write.
+    ///
+    /// The previous entry is overwritten without being dropped.
+    pub fn write_volatile(&mut self, index: usize, value: T) {
+        let entry: *mut T = &mut self.entries[index];
+        unsafe { core::ptr::write_volatile(entry, value) };
+    }
+
+    /// Returns `true` if the device is done with the entry at `index`,
+    /// as determined by `is_done`, e.g., by checking the entry's descriptor done (DD) bit.
+    ///
+    /// If so, an acquire barrier follows the check, such that later reads of the entry's other fields
+    /// and of the memory it describes (e.g., a receive buffer) cannot be reordered before it.
+    pub fn is_done<F: FnOnce(&T) -> bool>(&self, index: usize, is_done: F) -> bool {
+        let done = is_done(&self.entries[index]);
+        if done {
+            fence(Ordering::Acquire);
+        }
+        done
+    }
+
+    /// Hands all entries written so far to the device by invoking `notify` with the given `index`,
+    /// e.g., to write the new tail index to a queue's tail register or to ring a doorbell for that entry.
+    ///
+    /// A release barrier precedes `notify`, such that all prior writes to the entries
+    /// and to the memory they describe (e.g., a transmit buffer) are visible before the device is notified.
+    /// Returns the result of `notify`.
+    pub fn publish<F, R>(&self, index: usize, notify: F) -> R where F: FnOnce(usize) -> R {
+        fence(Ordering::Release);
+        notify(index)
+    }
+}
+
+impl<T: FromBytes> Deref for HwRing<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.entries
+    }
+}
+impl<T: FromBytes> DerefMut for HwRing<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.entries
+    }
+}
+
+/// Returns the index that is `count` entries after `index` in a ring of `len` entries.
+fn wrapping_add(index: usize, count: usize, len: usize) -> usize {
+    (index % len + count % len) % len
+}
+
+/// Returns the number of entries from `from` forward to `to` in a ring of `len` entries.
+fn distance(from: usize, to: usize, len: usize) -> usize {
+    (to % len + len - from % len) % len
+}
@@ -0,0 +1,53 @@
+//! Tests for the index arithmetic of a ring.
+
+use super::*;
+
+#[test]
+fn wrapping_add_within_ring() {
+    assert_eq!(wrapping_add(0, 1, 8), 1);
+    assert_eq!(wrapping_add(3, 4, 8), 7);
+}
+
+#[test]
+fn wrapping_add_wraps_around() {
+    assert_eq!(wrapping_add(7, 1, 8), 0);
+    assert_eq!(wrapping_add(6, 5, 8), 3);
+    assert_eq!(wrapping_add(2, 8, 8), 2);
+    assert_eq!(wrapping_add(0, 17, 8), 1);
+}
+
+#[test]
+fn wrapping_add_single_entry() {
+    assert_eq!(wrapping_add(0, 1, 1), 0);
+    assert_eq!(wrapping_add(0, 5, 1), 0);
+}
+
+#[test]
+fn wrapping_add_large_values() {
+    // `usize::MAX - 1` is 6 modulo 8, and the sum must not overflow.
+    assert_eq!(wrapping_add(usize::MAX - 1, usize::MAX - 1, 8), 4);
+    assert_eq!(wrapping_add(usize::MAX, 1, 8), 0);
+}
+
+#[test]
+fn distance_forward() {
+    assert_eq!(distance(0, 0, 8), 0);
+    assert_eq!(distance(2, 5, 8), 3);
+    assert_eq!(distance(0, 7, 8), 7);
+}
+
+#[test]
+fn distance_wraps_around() {
+    assert_eq!(distance(6, 1, 8), 3);
+    assert_eq!(distance(7, 0, 8), 1);
+    assert_eq!(distance(5, 4, 8), 7);
+}
+
+#[test]
+fn distance_inverts_wrapping_add() {
+    for from in 0..8 {
+        for count in 0..8 {
+            assert_eq!(distance(from, wrapping_add(from, count, 8), 8), count);
+        }
+    }
+}
@@ -39,6 +39,8 @@ pub const TX_PAYLEN_SHIFT:                 u8 = 46 - 32; //(actual offset - offs
 pub const RX_STATUS_DD:                    u8 = 1 << 0;
 /// Rx Status: End of Packet
 pub const RX_STATUS_EOP:                   u8 = 1 << 1;
+/// Advanced Rx Extended Status: the packet was time stamped by the NIC's time sync unit
+pub const RX_EXT_STATUS_TS:                u64 = 1 << 16;
 
 
 /// A trait for the minimum set of functions needed to receive a packet using one of Intel's receive descriptor types.
@@ -71,6 +73,12 @@ pub trait RxDescriptor: FromBytes {
 
     /// The length of the packet in the descriptor's packet buffer.
     fn length(&self) -> u64;
+
+    /// Returns true if the NIC latched a receive timestamp for the packet in this descriptor.
+    /// Descriptor types that don't report timestamps always return false.
+    fn timestamped(&self) -> bool {
+        false
+    }
 }
 
 /// A trait for the minimum set of functions needed to transmit a packet using one of Intel's transmit descriptor types.
@@ -88,10 +96,31 @@ pub trait TxDescriptor: FromBytes {
     /// # Arguments
     /// * `transmit_buffer_addr`: physical address of the transmit buffer. 
     /// * `transmit_buffer_length`: length of packet we want to send.
-    fn send(&mut self, transmit_buffer_addr: PhysicalAddress, transmit_buffer_length: u16);
+    fn send(&mut self, transmit_buffer_addr: PhysicalAddress, transmit_buffer_length: u16) {
+        self.send_batched(transmit_buffer_addr, transmit_buffer_length, true)
+    }
+
+    /// Updates the transmit descriptor to send the packet, 
+    /// but only asks the NIC to write back the descriptor's status if `report_status` is true.
+    /// 
+    /// Because the NIC processes descriptors in order, a write-back for one descriptor implies that 
+    /// all previous descriptors have also been sent, so the Report Status (RS) bit only needs
+    /// to be set on one out of every few descriptors.
+    /// 
+    /// # Arguments
+    /// * `transmit_buffer_addr`: physical address of the transmit buffer. 
+    /// * `transmit_buffer_length`: length of packet we want to send.
+    /// * `report_status`: whether the RS bit should be set in this descriptor.
+    fn send_batched(&mut self, transmit_buffer_addr: PhysicalAddress, transmit_buffer_length: u16, report_status: bool);
+
+    /// Returns true if the NIC has written back the Descriptor Done bit,
+    /// which is only done for descriptors that were sent with the RS bit set.
+    fn descriptor_done(&self) -> bool;
 
     /// Polls the Descriptor Done bit until the packet has been sent.
-    fn wait_for_packet_tx(&self);
+    fn wait_for_packet_tx(&self) {
+        while !self.descriptor_done() { }
+    }
 }
 
 
@@ -128,17 +157,16 @@ impl TxDescriptor for LegacyTxDescriptor {
         self.vlan.write(0);
     }
 
-    fn send(&mut self, transmit_buffer_addr: PhysicalAddress, transmit_buffer_length: u16) {
+    fn send_batched(&mut self, transmit_buffer_addr: PhysicalAddress, transmit_buffer_length: u16, report_status: bool) {
         self.phys_addr.write(transmit_buffer_addr.value() as u64);
         self.length.write(transmit_buffer_length);
-        self.cmd.write(TX_CMD_EOP | TX_CMD_IFCS | TX_CMD_RPS | TX_CMD_RS); 
+        let rs = if report_status { TX_CMD_RS } else { 0 };
+        self.cmd.write(TX_CMD_EOP | TX_CMD_IFCS | TX_CMD_RPS | rs); 
         self.status.write(0);
     }
 
-    fn wait_for_packet_tx(&self) {
-        while (self.status.read() & TX_STATUS_DD) == 0 {
-            // debug!("tx desc status: {}", self.status.read());
-        } 
+    fn descriptor_done(&self) -> bool {
+        (self.status.read() & TX_STATUS_DD) == TX_STATUS_DD
     }
 }
 
@@ -249,6 +277,10 @@ impl RxDescriptor for AdvancedRxDescriptor {
     fn length(&self) -> u64 {
         self.get_pkt_len() as u64
     }
+
+    fn timestamped(&self) -> bool {
+        (self.get_ext_status() & RX_EXT_STATUS_TS) == RX_EXT_STATUS_TS
+    }
 }
 
 impl AdvancedRxDescriptor {
@@ -369,18 +401,17 @@ impl TxDescriptor for AdvancedTxDescriptor {
         self.data_len.write(0);
     }
 
-    fn send(&mut self, transmit_buffer_addr: PhysicalAddress, transmit_buffer_length: u16) {
+    fn send_batched(&mut self, transmit_buffer_addr: PhysicalAddress, transmit_buffer_length: u16, report_status: bool) {
         self.packet_buffer_address.write(transmit_buffer_addr.value() as u64);
         self.data_len.write(transmit_buffer_length);
         self.dtyp_mac_rsv.write(TX_DTYP_ADV);
         self.paylen_popts_cc_idx_sta.write((transmit_buffer_length as u32) << TX_PAYLEN_SHIFT);
-        self.dcmd.write(TX_CMD_DEXT | TX_CMD_RS | TX_CMD_IFCS | TX_CMD_EOP);
+        let rs = if report_status { TX_CMD_RS } else { 0 };
+        self.dcmd.write(TX_CMD_DEXT | rs | TX_CMD_IFCS | TX_CMD_EOP);
     }
 
-    fn wait_for_packet_tx(&self) {
-        while (self.paylen_popts_cc_idx_sta.read() as u8 & TX_STATUS_DD) == 0 {
-            // error!("tx desc status: {:#X}", self.paylen_popts_cc_idx_sta.read());
-        } 
+    fn descriptor_done(&self) -> bool {
+        (self.paylen_popts_cc_idx_sta.read() as u8 & TX_STATUS_DD) == TX_STATUS_DD
     }
 }
 
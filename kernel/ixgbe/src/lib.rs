@@ -27,7 +27,7 @@ extern crate pic;
 extern crate acpi;
 extern crate volatile;
 extern crate mpmc;
-extern crate owning_ref;
+extern crate hw_ring;
 extern crate rand;
 extern crate hpet;
 extern crate runqueue;
@@ -52,28 +52,24 @@ use spin::Once;
 use alloc::{
     vec::Vec,
     collections::VecDeque,
-    sync::Arc,
-    boxed::Box,
 };
 use irq_safety::MutexIrqSafe;
-use memory::{PhysicalAddress, MappedPages};
-use pci::{PciDevice, MSIX_CAPABILITY, PciConfigSpaceAccessMechanism, PciLocation};
+use pci::{PciDevice, MSIX_CAPABILITY, PciConfigSpaceAccessMechanism, PciLocation, PciEnableGuard, PCI_COMMAND_MEMORY_SPACE, PCI_COMMAND_BUS_MASTER, BarRegisters};
 use bit_field::BitField;
 use interrupts::register_msi_interrupt;
 use x86_64::structures::idt::HandlerFunc;
 use hpet::get_hpet;
-use network_interface_card::NetworkInterfaceCard;
+use network_interface_card::{NetworkInterfaceCard, ReceiveFilter, LinkControl, LinkState, LinkSpeed, Duplex, LinkAbilities};
 use nic_initialization::*;
 use intel_ethernet::descriptors::{AdvancedRxDescriptor, AdvancedTxDescriptor};    
 use nic_buffers::{TransmitBuffer, ReceiveBuffer, ReceivedFrame};
 use nic_queues::{RxQueue, TxQueue};
-use owning_ref::BoxRefMut;
+use hw_ring::HwRing;
 use rand::{
     SeedableRng,
     RngCore,
     rngs::SmallRng
 };
-use core::mem::ManuallyDrop;
 use hashbrown::HashMap;
 
 /// Vendor ID for Intel
@@ -110,6 +106,8 @@ pub const IXGBE_NUM_RX_QUEUES_ENABLED:          u8      = 64;
 /// Do NOT set this greater than 64 since the queues 65-128 don't seem to work, 
 /// most likely because they need additional configuration.
 pub const IXGBE_NUM_TX_QUEUES_ENABLED:          u8      = 64;
+/// When sending a batch of packets, a status write-back is requested once every this many transmit descriptors.
+pub const IXGBE_TX_RS_THRESHOLD:                u16     = 32;
 
 
 
@@ -146,13 +144,13 @@ lazy_static! {
 
 /// A struct representing an ixgbe network interface card.
 pub struct IxgbeNic {
+    /// See [`PciEnableGuard`] for why this is the first field.
+    pci_enable_guard: PciEnableGuard,
     /// Device ID of the NIC assigned by the device manager.
     dev_id: PciLocation,
     /// Type of Base Address Register 0,
     /// if it's memory mapped or I/O.
     bar_type: u8,
-    /// MMIO Base Address     
-    mem_base: PhysicalAddress,
     /// Hashmap to store the interrupt number for each msi vector.
     /// The key is the id of the queue the interrupt is generated for,
     /// and the value is the interrupt number.
@@ -161,16 +159,18 @@ pub struct IxgbeNic {
     mac_hardware: [u8;6],       
     /// The optional spoofed MAC address to use in place of `mac_hardware` when transmitting.  
     mac_spoofed: Option<[u8; 6]>,
+    /// The largest frame payload that the NIC is configured to receive
+    mtu: u16,
     /// Memory-mapped control registers
-    regs1: BoxRefMut<MappedPages, IntelIxgbeRegisters1>,
+    regs1: BarRegisters<IntelIxgbeRegisters1>,
     /// Memory-mapped control registers
-    regs2: BoxRefMut<MappedPages, IntelIxgbeRegisters2>,
+    regs2: BarRegisters<IntelIxgbeRegisters2>,
     /// Memory-mapped control registers
-    regs3: BoxRefMut<MappedPages, IntelIxgbeRegisters3>,
+    regs3: BarRegisters<IntelIxgbeRegisters3>,
     /// Memory-mapped control registers
-    regs_mac: BoxRefMut<MappedPages, IntelIxgbeMacRegisters>,
+    regs_mac: BarRegisters<IntelIxgbeMacRegisters>,
     /// Memory-mapped msi-x vector table
-    msix_vector_table: BoxRefMut<MappedPages, MsixVectorTable>,
+    msix_vector_table: BarRegisters<MsixVectorTable>,
     /// Array to store which L3/L4 5-tuple filters have been used.
     /// There are 128 such filters available.
     l34_5_tuple_filters: [bool; 128],
@@ -208,12 +208,102 @@ impl NetworkInterfaceCard for IxgbeNic {
     fn poll_receive(&mut self) -> Result<(), &'static str> {
         // by default, when using the physical NIC interface, we receive on queue 0.
         let qid = 0;
-        self.rx_queues[qid].poll_queue_and_store_received_packets()
+        let regs2 = &mut self.regs2;
+        self.rx_queues[qid].poll_queue_and_store_received_packets_with_timestamps(|| Self::read_rx_timestamp(&mut *regs2))
     }
 
     fn mac_address(&self) -> [u8; 6] {
         self.mac_spoofed.unwrap_or(self.mac_hardware)
     }
+
+    fn mtu(&self) -> u16 {
+        self.mtu
+    }
+}
+
+// The 82599 only supports full-duplex links, and its MAC-level auto-negotiation is controlled through AUTOC.
+impl LinkControl for IxgbeNic {
+
+    fn link_state(&self) -> LinkState {
+        let links = self.regs2.links.read();
+        let up = links & LINKS_UP == LINKS_UP;
+        let speed = match LinkSpeedMbps::from_links_register_value(links & LINKS_SPEED_MASK) {
+            LinkSpeedMbps::LS100 => Some(LinkSpeed::Mbps100),
+            LinkSpeedMbps::LS1000 => Some(LinkSpeed::Mbps1000),
+            LinkSpeedMbps::LS10000 => Some(LinkSpeed::Mbps10000),
+            LinkSpeedMbps::LSUnknown => None,
+        };
+        LinkState {
+            up,
+            speed: if up { speed } else { None },
+            duplex: if up { Some(Duplex::Full) } else { None },
+        }
+    }
+
+    fn force_link(&mut self, speed: LinkSpeed, duplex: Duplex) -> Result<(), &'static str> {
+        if duplex != Duplex::Full {
+            return Err("ixgbe: the 82599 only supports full-duplex links");
+        }
+        let lms = match speed {
+            LinkSpeed::Mbps1000 => AUTOC_LMS_1_GB_NO_AN,
+            LinkSpeed::Mbps10000 => AUTOC_LMS_10_GBE_S,
+            _ => return Err("ixgbe: only 1 Gbps and 10 Gbps links can be forced"),
+        };
+        self.modify_autoc(|autoc| (autoc & !AUTOC_LMS_CLEAR) | lms | AUTOC_RESTART_AN)
+    }
+
+    fn restart_autonegotiation(&mut self) -> Result<(), &'static str> {
+        // Keep the current link mode, since switching an SFI (SFP+) port to a backplane mode would take its link down.
+        self.modify_autoc(|autoc| autoc | AUTOC_RESTART_AN)
+    }
+
+    fn link_partner_abilities(&mut self) -> Result<LinkAbilities, &'static str> {
+        Err("ixgbe: link partner abilities are not exposed by the 82599 for SFI links")
+    }
+}
+
+// Only the first receive address register is used, which holds the NIC's unicast MAC address.
+impl ReceiveFilter for IxgbeNic {
+
+    fn set_unicast_address(&mut self, mac_address: [u8; 6]) -> Result<(), &'static str> {
+        let low = u32::from_le_bytes([mac_address[0], mac_address[1], mac_address[2], mac_address[3]]);
+        let high = u32::from_le_bytes([mac_address[4], mac_address[5], 0, 0]);
+        // clear the Address Valid bit while the address is being changed
+        self.regs_mac.rah.write(0);
+        self.regs_mac.ral.write(low);
+        self.regs_mac.rah.write(high | RAH_AV);
+
+        self.mac_spoofed = if mac_address == self.mac_hardware { None } else { Some(mac_address) };
+        Ok(())
+    }
+
+    fn set_multicast_addresses(&mut self, multicast_addresses: &[[u8; 6]]) -> Result<(), &'static str> {
+        let mut mta = [0u32; IXGBE_NUM_MTA_REGS];
+        for addr in multicast_addresses {
+            let hash = Self::multicast_hash(addr);
+            mta[hash >> 5] |= 1 << (hash & 0x1F);
+        }
+        for (reg, value) in self.regs2.mta.iter_mut().zip(mta.iter()) {
+            reg.write(*value);
+        }
+        Ok(())
+    }
+
+    fn set_promiscuous(&mut self, enable: bool) -> Result<(), &'static str> {
+        let fctrl = self.regs2.fctrl.read();
+        let fctrl = if enable {
+            fctrl | UNICAST_PROMISCUOUS_ENABLE | MULTICAST_PROMISCUOUS_ENABLE
+        } else {
+            fctrl & !(UNICAST_PROMISCUOUS_ENABLE | MULTICAST_PROMISCUOUS_ENABLE)
+        };
+        // FCTRL may only be changed while receive is disabled (8.2.3.7.1),
+        // after which receive is restored to its prior state rather than unconditionally enabled.
+        let rxctrl = self.regs2.rxctrl.read();
+        Self::disable_rx_function(&mut self.regs2);
+        self.regs2.fctrl.write(fctrl);
+        self.regs2.rxctrl.write(rxctrl);
+        Ok(())
+    }
 }
 
 // Functions that setup the NIC struct and handle the sending and receiving of packets.
@@ -236,6 +326,9 @@ impl IxgbeNic {
     ///     If interrupts are disabled, this should be set to None.
     /// * `enable_rss`: true if receive side scaling is enabled.
     /// * `rx_buffer_size_kbytes`: The size of receive buffers. 
+    /// * `mtu`: The maximum size of a packet's payload, up to [`MAX_JUMBO_MTU`].
+    ///     If this is larger than [`STANDARD_MTU`], jumbo frames are enabled,
+    ///     and frames that are larger than a receive buffer span multiple receive descriptors.
     /// * `num_rx_descriptors`: The number of descriptors in each receive queue.
     /// * `num_tx_descriptors`: The number of descriptors in each transmit queue.
     pub fn init(
@@ -245,6 +338,7 @@ impl IxgbeNic {
         interrupts: Option<Vec<HandlerFunc>>,
         enable_rss: bool,
         rx_buffer_size_kbytes: RxBufferSizeKiB,
+        mtu: u16,
         num_rx_descriptors: u16,
         num_tx_descriptors: u16
     ) -> Result<MutexIrqSafe<IxgbeNic>, &'static str> {
@@ -287,15 +381,12 @@ impl IxgbeNic {
             return Err("ixgbe::init(): BAR0 is of I/O type")
         }
 
-        // 16-byte aligned memory mapped base address
-        let mem_base =  ixgbe_pci_dev.determine_mem_base(0)?;
-
-        // set the bus mastering bit for this PciDevice, which allows it to use DMA
-        ixgbe_pci_dev.pci_set_command_bus_master_bit();
+        // set the memory space and bus mastering bits for this PciDevice, which allows it to use MMIO and DMA
+        let pci_enable_guard = ixgbe_pci_dev.pci_enable(PCI_COMMAND_MEMORY_SPACE | PCI_COMMAND_BUS_MASTER, "ixgbe");
 
         // map the IntelIxgbeRegisters structs to the address found from the pci space
         let (mut mapped_registers1, mut mapped_registers2, mut mapped_registers3, mut mapped_registers_mac, 
-            mut rx_mapped_registers, mut tx_mapped_registers) = Self::mapped_reg(mem_base)?;
+            mut rx_mapped_registers, mut tx_mapped_registers) = Self::mapped_reg(ixgbe_pci_dev)?;
 
         // map the msi-x vector table to an address found from the pci space
         let mut vector_table = Self::mem_map_msix(ixgbe_pci_dev)?;
@@ -313,7 +404,7 @@ impl IxgbeNic {
         init_rx_buf_pool(RX_BUFFER_POOL_SIZE, rx_buffer_size_kbytes as u16 * 1024, &RX_BUFFER_POOL)?;
 
         // create the rx desc queues and their packet buffers
-        let (mut rx_descs, mut rx_buffers) = Self::rx_init(&mut mapped_registers1, &mut mapped_registers2, &mut rx_mapped_registers, num_rx_descriptors, rx_buffer_size_kbytes)?;
+        let (mut rx_descs, mut rx_buffers) = Self::rx_init(&mut mapped_registers1, &mut mapped_registers2, &mut rx_mapped_registers, num_rx_descriptors, rx_buffer_size_kbytes, mtu)?;
         
         // create the vec of rx queues
         let mut rx_queues = Vec::with_capacity(rx_descs.len());
@@ -323,7 +414,6 @@ impl IxgbeNic {
                 id: id,
                 regs: rx_mapped_registers.remove(0),
                 rx_descs: rx_descs.remove(0),
-                num_rx_descs: num_rx_descriptors,
                 rx_cur: 0,
                 rx_bufs_in_use: rx_buffers.remove(0),  
                 rx_buffer_size_bytes: rx_buffer_size_kbytes as u16 * 1024,
@@ -348,9 +438,10 @@ impl IxgbeNic {
                 id: id,
                 regs: tx_mapped_registers.remove(0),
                 tx_descs: tx_descs.remove(0),
-                num_tx_descs: num_tx_descriptors,
                 tx_cur: 0,
                 cpu_id : None,
+                tx_bufs_in_flight: VecDeque::new(),
+                rs_threshold: IXGBE_TX_RS_THRESHOLD,
             };
             tx_queues.push(tx_queue);
             id += 1;
@@ -376,12 +467,13 @@ impl IxgbeNic {
         Self::wait_for_link(&mapped_registers2, 10_000_000);
 
         let ixgbe_nic = IxgbeNic {
+            pci_enable_guard,
             dev_id: dev_id,
             bar_type: bar_type,
-            mem_base: mem_base,
             interrupt_num: interrupt_num,
             mac_hardware: mac_addr_hardware,
             mac_spoofed: None,
+            mtu,
             regs1: mapped_registers1,
             regs2: mapped_registers2,
             regs3: mapped_registers3,
@@ -408,12 +500,12 @@ impl IxgbeNic {
 
     /// Returns the memory-mapped control registers of the nic and the rx/tx queue registers.
     fn mapped_reg(
-        mem_base: PhysicalAddress
+        dev: &PciDevice
     ) -> Result<(
-        BoxRefMut<MappedPages, IntelIxgbeRegisters1>, 
-        BoxRefMut<MappedPages, IntelIxgbeRegisters2>, 
-        BoxRefMut<MappedPages, IntelIxgbeRegisters3>, 
-        BoxRefMut<MappedPages, IntelIxgbeMacRegisters>, 
+        BarRegisters<IntelIxgbeRegisters1>, 
+        BarRegisters<IntelIxgbeRegisters2>, 
+        BarRegisters<IntelIxgbeRegisters3>, 
+        BarRegisters<IntelIxgbeMacRegisters>, 
         Vec<IxgbeRxQueueRegisters>, 
         Vec<IxgbeTxQueueRegisters>
     ), &'static str> {
@@ -424,124 +516,75 @@ impl IxgbeNic {
         const GENERAL_REGISTERS_2_SIZE_BYTES:   usize = 4 * 4096;
         const TX_REGISTERS_SIZE_BYTES:          usize = 2 * 4096;
         const MAC_REGISTERS_SIZE_BYTES:         usize = 5 * 4096;
-        const GENERAL_REGISTERS_3_SIZE_BYTES:   usize = 18 * 4096;
 
-        // Allocate memory for the registers, making sure each successive memory region begins where the previous region ended.
-        let mut offset = mem_base;
-        let nic_regs1_mapped_page = allocate_memory(offset, GENERAL_REGISTERS_1_SIZE_BYTES)?;
+        // Map the registers within BAR0, making sure each successive memory region begins where the previous region ended.
+        let mut offset = 0;
+        let regs1 = dev.pci_bar_registers::<IntelIxgbeRegisters1>(0, offset)?;
 
         offset += GENERAL_REGISTERS_1_SIZE_BYTES;
-        let nic_rx_regs1_mapped_page = allocate_memory(offset, RX_REGISTERS_SIZE_BYTES)?;
+        // Divide the Rx queue registers into multiple 64B regions
+        let mut regs_rx = Self::mapped_regs_from_rx_memory(dev, offset)?;
 
         offset += RX_REGISTERS_SIZE_BYTES;
-        let nic_regs2_mapped_page = allocate_memory(offset, GENERAL_REGISTERS_2_SIZE_BYTES)?;  
+        let regs2 = dev.pci_bar_registers::<IntelIxgbeRegisters2>(0, offset)?;
 
         offset += GENERAL_REGISTERS_2_SIZE_BYTES;
-        let nic_tx_regs_mapped_page = allocate_memory(offset, TX_REGISTERS_SIZE_BYTES)?;
+        // Divide the Tx queue registers into multiple 64B regions
+        let regs_tx = Self::mapped_regs_from_tx_memory(dev, offset)?;
 
         offset += TX_REGISTERS_SIZE_BYTES;
-        let nic_mac_regs_mapped_page = allocate_memory(offset, MAC_REGISTERS_SIZE_BYTES)?;
+        let mac_regs = dev.pci_bar_registers::<IntelIxgbeMacRegisters>(0, offset)?;
 
         offset += MAC_REGISTERS_SIZE_BYTES;
-        let nic_rx_regs2_mapped_page = allocate_memory(offset, RX_REGISTERS_SIZE_BYTES)?;   
+        regs_rx.append(&mut Self::mapped_regs_from_rx_memory(dev, offset)?);
 
         offset += RX_REGISTERS_SIZE_BYTES;
-        let nic_regs3_mapped_page = allocate_memory(offset, GENERAL_REGISTERS_3_SIZE_BYTES)?;
-
-        // Map the memory as the register struct and tie the lifetime of the struct with its backing mapped pages
-        let regs1 = BoxRefMut::new(Box::new(nic_regs1_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<IntelIxgbeRegisters1>(0))?;
-        let regs2 = BoxRefMut::new(Box::new(nic_regs2_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<IntelIxgbeRegisters2>(0))?;
-        let regs3 = BoxRefMut::new(Box::new(nic_regs3_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<IntelIxgbeRegisters3>(0))?;
-        let mac_regs = BoxRefMut::new(Box::new(nic_mac_regs_mapped_page)).try_map_mut(|mp| mp.as_type_mut::<IntelIxgbeMacRegisters>(0))?;
-        
-        // Divide the pages of the Rx queue registers into multiple 64B regions
-        let mut regs_rx = Self::mapped_regs_from_rx_memory(nic_rx_regs1_mapped_page);
-        regs_rx.append(&mut Self::mapped_regs_from_rx_memory(nic_rx_regs2_mapped_page));
-        
-        // Divide the pages of the Tx queue registers into multiple 64B regions
-        let regs_tx = Self::mapped_regs_from_tx_memory(nic_tx_regs_mapped_page);
+        let regs3 = dev.pci_bar_registers::<IntelIxgbeRegisters3>(0, offset)?;
             
         Ok((regs1, regs2, regs3, mac_regs, regs_rx, regs_tx))
     }
 
-    /// Split the pages where rx queue registers are mapped into multiple smaller memory regions.
+    /// Split the region of BAR0 starting at `offset` where rx queue registers are located into multiple smaller memory regions.
     /// One region contains all the registers for a single queue.
-    fn mapped_regs_from_rx_memory(mp: MappedPages) -> Vec<IxgbeRxQueueRegisters> {
-        const QUEUES_IN_MP: usize = 64;
+    fn mapped_regs_from_rx_memory(dev: &PciDevice, offset: usize) -> Result<Vec<IxgbeRxQueueRegisters>, &'static str> {
+        const QUEUES_IN_REGION: usize = 64;
         const RX_QUEUE_REGISTERS_SIZE_BYTES: usize = core::mem::size_of::<RegistersRx>();
-        
-        assert!(mp.size_in_bytes() >= QUEUES_IN_MP * RX_QUEUE_REGISTERS_SIZE_BYTES);
-
-        let starting_address = mp.start_address();
-
-        // We share the backing mapped pages among all the queue registers
-        let shared_mp = Arc::new(mp);
-        let mut pointers_to_queues = Vec::with_capacity(QUEUES_IN_MP);
-
-        for i in 0..QUEUES_IN_MP {
-            // This is safe because we have checked that the number of queues we want to partition from these mapped pages fit into the allocated memory,
-            // and that each queue starts at the end of the previous.
-            // We also ensure that the backing mapped pages are included in the same struct as the registers, almost as a pseudo OwningRef
-            let registers = unsafe{ Box::from_raw((starting_address.value() + (i * RX_QUEUE_REGISTERS_SIZE_BYTES)) as *mut RegistersRx) };
-            pointers_to_queues.push(
-                IxgbeRxQueueRegisters {
-                    regs: ManuallyDrop::new(registers),
-                    backing_pages: shared_mp.clone()
-                }
-            );
+
+        let mut pointers_to_queues = Vec::with_capacity(QUEUES_IN_REGION);
+        for i in 0..QUEUES_IN_REGION {
+            let registers = dev.pci_bar_registers::<RegistersRx>(0, offset + (i * RX_QUEUE_REGISTERS_SIZE_BYTES))?;
+            pointers_to_queues.push(IxgbeRxQueueRegisters { regs: registers });
         }
-        pointers_to_queues
+        Ok(pointers_to_queues)
     }
 
-    /// Split the pages where tx queue registers are mapped into multiple smaller memory regions.
+    /// Split the region of BAR0 starting at `offset` where tx queue registers are located into multiple smaller memory regions.
     /// One region contains all the registers for a single queue.
-    fn mapped_regs_from_tx_memory(mp: MappedPages) -> Vec<IxgbeTxQueueRegisters> {
-        const QUEUES_IN_MP: usize = 128;
+    fn mapped_regs_from_tx_memory(dev: &PciDevice, offset: usize) -> Result<Vec<IxgbeTxQueueRegisters>, &'static str> {
+        const QUEUES_IN_REGION: usize = 128;
         const TX_QUEUE_REGISTERS_SIZE_BYTES: usize = core::mem::size_of::<RegistersTx>();
-        
-        assert!(mp.size_in_bytes() >= QUEUES_IN_MP * TX_QUEUE_REGISTERS_SIZE_BYTES);
-
-        let starting_address = mp.start_address();
-
-        // We share the backing mapped pages among all the queue registers
-        let shared_mp = Arc::new(mp);
-        let mut pointers_to_queues = Vec::with_capacity(QUEUES_IN_MP);
-
-        for i in 0..QUEUES_IN_MP {
-            // This is safe because we have checked that the number of queues we want to partition from these mapped pages fit into the allocated memory,
-            // and that each queue starts at the end of the previous.
-            // We also ensure that the backing mapped pages are included in the same struct as the registers, almost as a pseudo OwningRef
-            let registers = unsafe{ Box::from_raw((starting_address.value() + (i * TX_QUEUE_REGISTERS_SIZE_BYTES)) as *mut RegistersTx) };
-            pointers_to_queues.push(
-                IxgbeTxQueueRegisters {
-                    regs: ManuallyDrop::new(registers),
-                    backing_pages: shared_mp.clone()
-                }
-            );
+
+        let mut pointers_to_queues = Vec::with_capacity(QUEUES_IN_REGION);
+        for i in 0..QUEUES_IN_REGION {
+            let registers = dev.pci_bar_registers::<RegistersTx>(0, offset + (i * TX_QUEUE_REGISTERS_SIZE_BYTES))?;
+            pointers_to_queues.push(IxgbeTxQueueRegisters { regs: registers });
         }
-        pointers_to_queues
+        Ok(pointers_to_queues)
     }
 
     /// Returns the memory mapped msix vector table
-    pub fn mem_map_msix(dev: &PciDevice) -> Result<BoxRefMut<MappedPages, MsixVectorTable>, &'static str> {
+    pub fn mem_map_msix(dev: &PciDevice) -> Result<BarRegisters<MsixVectorTable>, &'static str> {
         // retreive the address in the pci config space for the msi-x capability
         let cap_addr = dev.find_pci_capability(MSIX_CAPABILITY).ok_or("ixgbe: device does not have MSI-X capability")?;
-        // find the BAR used for msi-x
+        // find the BAR used for msi-x, and the offset of the vector table within that BAR
         let vector_table_offset = 4;
         let table_offset = dev.pci_read_32(cap_addr + vector_table_offset);
         let bar = table_offset & 0x7;
-        let offset = table_offset >> 3;
-        // find the memory base address and size of the area for the vector table
-        let mem_base = PhysicalAddress::new((dev.bars[bar as usize] + offset) as usize)
-            .ok_or("ixgbe: the mem_base physical address specified in the BAR was invalid")?;
-        let mem_size_in_bytes = core::mem::size_of::<MsixVectorEntry>() * IXGBE_MAX_MSIX_VECTORS;
+        let offset = table_offset & !0x7;
 
-        // debug!("msi-x vector table bar: {}, base_address: {:#X} and size: {} bytes", bar, mem_base, mem_size_in_bytes);
+        // debug!("msi-x vector table bar: {}, offset: {:#X}", bar, offset);
 
-        let msix_mapped_pages = allocate_memory(mem_base, mem_size_in_bytes)?;
-        let vector_table = BoxRefMut::new(Box::new(msix_mapped_pages)).try_map_mut(|mp| mp.as_type_mut::<MsixVectorTable>(0))?;
-
-        Ok(vector_table)
+        dev.pci_bar_registers::<MsixVectorTable>(bar as usize, offset as usize)
     }
 
     pub fn spoof_mac(&mut self, spoofed_mac_addr: [u8; 6]) {
@@ -565,6 +608,13 @@ impl IxgbeNic {
         mac_addr
     }   
 
+    /// Returns the index of the bit in the Multicast Table Array that corresponds to the given multicast address.
+    /// 
+    /// This uses the hash function selected by `MCSTCTRL_MO_47_36`, i.e., bits 47:36 of the address.
+    fn multicast_hash(mac_address: &[u8; 6]) -> usize {
+        ((mac_address[4] as usize >> 4) | ((mac_address[5] as usize) << 4)) & 0xFFF
+    }
+
     /// Acquires semaphore to synchronize between software and firmware (10.5.4)
     fn acquire_semaphore(regs: &mut IntelIxgbeRegisters3) -> Result<bool, &'static str> {
         // femtoseconds per millisecond
@@ -736,6 +786,75 @@ impl IxgbeNic {
         Ok(())
     }
 
+    /// Updates the AUTOC register with the value returned by `f`,
+    /// while holding the software/firmware semaphore that guards the MAC's link configuration.
+    /// 
+    /// Returns an error if the semaphore couldn't be acquired after 100 tries, 10 ms apart.
+    fn modify_autoc<F: FnOnce(u32) -> u32>(&mut self, f: F) -> Result<(), &'static str> {
+        // wait 10 ms between tries
+        let wait_time = 10_000;
+        let max_tries = 100;
+        let mut tries = 0;
+        while !Self::acquire_semaphore(&mut self.regs3)? {
+            tries += 1;
+            if tries >= max_tries {
+                return Err("ixgbe: timed out acquiring the semaphore needed to modify AUTOC");
+            }
+            let _ = pit_clock::pit_wait(wait_time);
+        }
+
+        let autoc = self.regs2.autoc.read();
+        self.regs2.autoc.write(f(autoc));
+
+        Self::release_semaphore(&mut self.regs3);
+        Ok(())
+    }
+
+    /// Enables hardware timestamping of received PTP packets, which are then
+    /// available through [`ReceivedFrame::timestamp()`] for frames received on queue 0.
+    /// 
+    /// The system time is reset to 0 and its increment is set for a 10 Gbps link.
+    /// The 82599 can only latch one receive timestamp at a time, 
+    /// so a PTP packet that arrives before the previous timestamp was read is not timestamped.
+    pub fn enable_rx_timestamps(&mut self) {
+        self.regs_mac.timinca.write(TIMINCA_10GB);
+        self.regs_mac.systiml.write(0);
+        self.regs_mac.systimh.write(0);
+
+        // identify L2 PTP packets as candidates for timestamping
+        self.regs2.etqf[ETQF_FILTER_1588].write(ETQF_FILTER_EN | ETQF_1588 | ETHERTYPE_1588);
+
+        // reading the high register unlocks any previously-latched timestamp
+        self.regs2.rxstmph.read();
+        self.regs2.tsyncrxctl.write(TSYNCRXCTL_EN | TSYNCRXCTL_TYPE_L2_L4_V2);
+    }
+
+    /// Disables hardware timestamping of received packets.
+    pub fn disable_rx_timestamps(&mut self) {
+        self.regs2.tsyncrxctl.write(0);
+        self.regs2.etqf[ETQF_FILTER_1588].write(0);
+    }
+
+    /// Returns the current value of the NIC's system time in nanoseconds,
+    /// which is the same clock used to timestamp received packets.
+    pub fn system_time_ns(&self) -> u64 {
+        // reading the low register latches the high register
+        let low = self.regs_mac.systiml.read() as u64;
+        let high = self.regs_mac.systimh.read() as u64;
+        ((high << 32) | low) >> SYSTIM_NS_SHIFT
+    }
+
+    /// Reads the latched receive timestamp in nanoseconds, if there is a valid one.
+    /// Reading the timestamp allows the NIC to latch the timestamp of the next PTP packet.
+    fn read_rx_timestamp(regs2: &mut IntelIxgbeRegisters2) -> Option<u64> {
+        if regs2.tsyncrxctl.read() & TSYNCRXCTL_VALID == 0 {
+            return None;
+        }
+        let low = regs2.rxstmpl.read() as u64;
+        let high = regs2.rxstmph.read() as u64;
+        Some(((high << 32) | low) >> SYSTIM_NS_SHIFT)
+    }
+
     /// Returns value of (links, links2) registers
     pub fn link_status(&self) -> (u32, u32) {
         (self.regs2.links.read(), self.regs2.links2.read())
@@ -788,15 +907,13 @@ impl IxgbeNic {
         regs: &mut IntelIxgbeRegisters2, 
         rx_regs: &mut Vec<IxgbeRxQueueRegisters>,
         num_rx_descs: u16,
-        rx_buffer_size_kbytes: RxBufferSizeKiB
+        rx_buffer_size_kbytes: RxBufferSizeKiB,
+        mtu: u16
     ) -> Result<(
-        Vec<BoxRefMut<MappedPages, [AdvancedRxDescriptor]>>, 
+        Vec<HwRing<AdvancedRxDescriptor>>, 
         Vec<Vec<ReceiveBuffer>>
     ), &'static str> {
 
-        let mut rx_descs_all_queues = Vec::new();
-        let mut rx_bufs_in_use_all_queues = Vec::new();
-
         Self::disable_rx_function(regs);
         // program RXPBSIZE according to DCB and virtualization modes (both off)
         regs.rxpbsize[0].write(RXPBSIZE_512KB);
@@ -809,12 +926,20 @@ impl IxgbeNic {
         // Clear bits
         regs.rdrxctl.write(regs.rdrxctl.read() & !RDRXCTL_RSCFRSTSIZE);
 
-        for qid in 0..IXGBE_NUM_RX_QUEUES_ENABLED {
-            let rxq = &mut rx_regs[qid as usize];        
+        // set the largest frame that will be accepted, which requires enabling jumbo frames beyond the standard MTU
+        let frame_size = max_frame_size(mtu)?;
+        if mtu > STANDARD_MTU {
+            regs.maxfrs.write((frame_size as u32) << MAXFRS_MFS_SHIFT);
+            regs.hlreg0.write(regs.hlreg0.read() | HLREG0_JUMBOEN);
+        } else {
+            regs.hlreg0.write(regs.hlreg0.read() & !HLREG0_JUMBOEN);
+        }
+
+        // get the queue of rx descriptors and their corresponding rx buffers for each enabled queue
+        let num_queues = IXGBE_NUM_RX_QUEUES_ENABLED as usize;
+        let rx_queues = init_rx_queues(num_rx_descs as usize, &RX_BUFFER_POOL, rx_buffer_size_kbytes as usize * 1024, &mut rx_regs[..num_queues])?;
 
-            // get the queue of rx descriptors and their corresponding rx buffers
-            let (rx_descs, rx_bufs_in_use) = init_rx_queue(num_rx_descs as usize, &RX_BUFFER_POOL, rx_buffer_size_kbytes as usize * 1024, rxq)?;          
-            
+        for rxq in rx_regs[..num_queues].iter_mut() {
             //set the size of the packet buffers and the descriptor format used
             let mut val = rxq.srrctl.read();
             val.set_bits(0..4, rx_buffer_size_kbytes as u32);
@@ -838,10 +963,8 @@ impl IxgbeNic {
             // Note that the 82599 datasheet (section 8.2.3.8.5) states that we should set the RDT (tail index) to the index *beyond* the last receive descriptor, 
             // but we set it to the last receive descriptor for the same reason as the e1000 driver
             rxq.rdt.write((num_rx_descs - 1) as u32);
-            
-            rx_descs_all_queues.push(rx_descs);
-            rx_bufs_in_use_all_queues.push(rx_bufs_in_use);
         }
+        let (rx_descs_all_queues, rx_bufs_in_use_all_queues) = rx_queues.into_iter().unzip();
         
         Self::enable_rx_function(regs1,regs);
         Ok((rx_descs_all_queues, rx_bufs_in_use_all_queues))
@@ -858,6 +981,13 @@ impl IxgbeNic {
         // set rx parameters of which type of packets are accepted by the nic
         // right now we allow the nic to receive all types of packets, even incorrectly formed ones
         regs.fctrl.write(STORE_BAD_PACKETS | MULTICAST_PROMISCUOUS_ENABLE | UNICAST_PROMISCUOUS_ENABLE | BROADCAST_ACCEPT_MODE); 
+
+        // start with an empty multicast table, so that only the groups later added through `ReceiveFilter` 
+        // are accepted once promiscuous mode is disabled
+        for mta in regs.mta.iter_mut() {
+            mta.write(0);
+        }
+        regs.mcstctrl.write(MCSTCTRL_MFE | MCSTCTRL_MO_47_36);
         
         // some magic numbers
         regs1.ctrl_ext.write(regs1.ctrl_ext.read() | CTRL_EXT_NO_SNOOP_DIS);
@@ -874,7 +1004,7 @@ impl IxgbeNic {
         regs_mac: &mut IntelIxgbeMacRegisters, 
         tx_regs: &mut Vec<IxgbeTxQueueRegisters>,
         num_tx_descs: u16
-    ) -> Result<Vec<BoxRefMut<MappedPages, [AdvancedTxDescriptor]>>, &'static str> {
+    ) -> Result<Vec<HwRing<AdvancedTxDescriptor>>, &'static str> {
         // disable transmission
         Self::disable_transmission(regs);
 
@@ -894,22 +1024,16 @@ impl IxgbeNic {
         // Clear RTTFCS.ARBDIS
         regs.rttdcs.write(regs.rttdcs.read() & !RTTDCS_ARBDIS);
 
-        let mut tx_descs_all_queues = Vec::new();
-        
-        for qid in 0..IXGBE_NUM_TX_QUEUES_ENABLED {
-            let txq = &mut tx_regs[qid as usize];
+        // create the tx descriptors for each enabled queue
+        let num_queues = IXGBE_NUM_TX_QUEUES_ENABLED as usize;
+        let tx_descs_all_queues = init_tx_queues(num_tx_descs as usize, &mut tx_regs[..num_queues])?;
 
-            let tx_descs = init_tx_queue(num_tx_descs as usize, txq)?;
-        
-            if qid == 0 {
-                // enable transmit operation, only have to do this for the first queue
-                Self::enable_transmission(regs);
-            }
+        // enable transmit operation, which must be done before the individual queues are enabled
+        Self::enable_transmission(regs);
 
-            // Set descriptor thresholds
-            // If we enable this then we need to change the packet send function to stop polling
-            // for a descriptor done on every packet sent
-            // txq.txdctl.write(TXDCTL_PTHRESH | TXDCTL_HTHRESH | TXDCTL_WTHRESH); 
+        for txq in tx_regs[..num_queues].iter_mut() {
+            // Descriptor thresholds are left at 0 by default, such that each RS descriptor is written back immediately.
+            // They can be changed later with `set_tx_descriptor_thresholds()`.
 
             //enable tx queue
             let val = txq.txdctl.read();
@@ -917,12 +1041,31 @@ impl IxgbeNic {
 
             //make sure queue is enabled
             while txq.txdctl.read() & TX_Q_ENABLE == 0 {} 
-
-            tx_descs_all_queues.push(tx_descs);
         }
         Ok(tx_descs_all_queues)
     }  
 
+    /// Sets the prefetch, host, and write-back thresholds of the transmit queue `qid`.
+    /// 
+    /// A non-zero `wthresh` makes the NIC accumulate that many completed descriptors 
+    /// before writing them back together, which reduces PCIe traffic.
+    /// However, completions are then delayed until enough descriptors have accumulated,
+    /// so this should only be used when packets are sent in a continuous stream 
+    /// (e.g., with [`TxQueue::send_batch()`]) rather than one at a time with `send_packet()`.
+    /// The DPDK values are `TXDCTL_PTHRESH`, `TXDCTL_HTHRESH`, and `TXDCTL_WTHRESH`.
+    pub fn set_tx_descriptor_thresholds(&mut self, qid: usize, pthresh: u8, hthresh: u8, wthresh: u8) -> Result<(), &'static str> {
+        if pthresh > 0x7F || hthresh > 0x7F || wthresh > 0x7F {
+            return Err("ixgbe: transmit descriptor thresholds must fit in 7 bits");
+        }
+        let txq = &mut self.tx_queues.get_mut(qid).ok_or("ixgbe: invalid transmit queue id")?.regs;
+        let mut val = txq.txdctl.read();
+        val.set_bits(0..7, pthresh as u32);
+        val.set_bits(8..15, hthresh as u32);
+        val.set_bits(16..23, wthresh as u32);
+        txq.txdctl.write(val);
+        Ok(())
+    }
+
     /// disable transmit functionality
     fn disable_transmission(regs: &mut IntelIxgbeRegisters2) {
         let val = regs.dmatxctl.read();
@@ -941,34 +1084,15 @@ impl IxgbeNic {
         regs2: &mut IntelIxgbeRegisters2, 
         regs3: &mut IntelIxgbeRegisters3
     ) -> Result<(), &'static str> {
-        // enable RSS writeback in the header field of the receive descriptor
-        regs2.rxcsum.write(RXCSUM_PCSD);
-        
-        // enable RSS and set fields that will be used by hash function
-        // right now we're using the udp port and ipv4 address.
-        regs3.mrqc.write(MRQC_MRQE_RSS | MRQC_UDPIPV4 ); 
-
         //set the random keys for the hash function
         let seed = get_hpet().as_ref().ok_or("couldn't get HPET timer")?.get_counter();
         let mut rng = SmallRng::seed_from_u64(seed);
-        for rssrk in regs3.rssrk.iter_mut() {
-            rssrk.write(rng.next_u32());
-        }
+        let mut hash_key = [0u8; IXGBE_RSS_HASH_KEY_LEN];
+        rng.fill_bytes(&mut hash_key);
 
-        // Initialize the RSS redirection table
-        // each reta register has 4 redirection entries
-        // since mapping to queues is random and based on a hash, we randomly assign 1 queue to each reta register
-        let mut qid = 0;
-        for reta in regs3.reta.iter_mut() {
-            //set 4 entries to the same queue number
-            let val = qid << RETA_ENTRY_0_OFFSET | qid << RETA_ENTRY_1_OFFSET | qid << RETA_ENTRY_2_OFFSET | qid << RETA_ENTRY_3_OFFSET;
-            reta.write(val);
-
-            // next 4 entries will be assigned to the next queue
-            qid = (qid + 1) % IXGBE_NUM_RX_QUEUES_ENABLED as u32;
-        }
-
-        Ok(())
+        // Initialize the RSS redirection table and hash key, and enable RSS
+        let num_queues = core::cmp::min(IXGBE_NUM_RX_QUEUES_ENABLED, IXGBE_RSS_MAX_QUEUES);
+        init_rss(&mut IxgbeRssRegisters { regs2, regs3 }, num_queues, &hash_key)
     }
 
     /// Enables Direct Cache Access for the device.
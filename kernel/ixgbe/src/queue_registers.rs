@@ -1,26 +1,19 @@
-//! Structs which provide access to the ixgbe device queue registers and store their backing memory pages.
+//! Structs which provide access to the ixgbe device queue registers, which are located in the NIC's memory-mapped BAR0.
 //! 
 //! They implement the `RxQueueRegisters` and `TxQueueRegisters` traits which allows 
-//! the registers to be accessed through virtual NICs
+//! the registers to be accessed through virtual NICs.
+//! The RSS registers implement the `RssRegisters` trait.
 
-use super::regs::{RegistersRx, RegistersTx};
-use alloc::{
-    sync::Arc,
-    boxed::Box
-};
+use super::regs::*;
 use core::ops::{Deref, DerefMut};
-use core::mem::ManuallyDrop;
-use nic_queues::{RxQueueRegisters, TxQueueRegisters};
-use memory::MappedPages;
+use nic_queues::{RxQueueRegisters, TxQueueRegisters, RssRegisters};
+use pci::BarRegisters;
 
 
-/// Struct that stores a pointer to registers for one ixgbe receive queue
-/// as well as a shared reference to the backing `MappedPages` where these registers are located.
+/// Struct that stores the registers for one ixgbe receive queue,
+/// which share the backing `MappedPages` of BAR0 with the NIC's other registers.
 pub struct IxgbeRxQueueRegisters {
-    /// We prevent the drop handler from dropping the `regs` because the backing memory is not in the heap,
-    /// but in the stored mapped pages. The memory will be deallocated when the `backing_pages` are dropped.
-    pub regs: ManuallyDrop<Box<RegistersRx>>,
-    pub backing_pages: Arc<MappedPages>
+    pub regs: BarRegisters<RegistersRx>,
 }
 
 impl RxQueueRegisters for IxgbeRxQueueRegisters {
@@ -41,24 +34,21 @@ impl RxQueueRegisters for IxgbeRxQueueRegisters {
     }
 }
 impl Deref for IxgbeRxQueueRegisters {
-    type Target = Box<RegistersRx>;
-    fn deref(&self) -> &Box<RegistersRx> {
+    type Target = RegistersRx;
+    fn deref(&self) -> &RegistersRx {
         &self.regs
     }
 }
 impl DerefMut for IxgbeRxQueueRegisters {
-    fn deref_mut(&mut self) -> &mut Box<RegistersRx> {
+    fn deref_mut(&mut self) -> &mut RegistersRx {
         &mut self.regs
     }
 }
 
-/// Struct that stores a pointer to registers for one ixgbe transmit queue
-/// as well as a shared reference to the backing `MappedPages` where these registers are located.
+/// Struct that stores the registers for one ixgbe transmit queue,
+/// which share the backing `MappedPages` of BAR0 with the NIC's other registers.
 pub struct IxgbeTxQueueRegisters {
-    /// We prevent the drop handler from dropping the `regs` because the backing memory is not in the heap,
-    /// but in the stored mapped pages. The memory will be deallocated when the `backing_pages` are dropped.
-    pub regs: ManuallyDrop<Box<RegistersTx>>,
-    pub backing_pages: Arc<MappedPages>
+    pub regs: BarRegisters<RegistersTx>,
 }
 impl TxQueueRegisters for IxgbeTxQueueRegisters {
     fn set_tdbal(&mut self, value: u32) {
@@ -78,13 +68,46 @@ impl TxQueueRegisters for IxgbeTxQueueRegisters {
     }
 }
 impl Deref for IxgbeTxQueueRegisters {
-    type Target = Box<RegistersTx>;
-    fn deref(&self) -> &Box<RegistersTx> {
+    type Target = RegistersTx;
+    fn deref(&self) -> &RegistersTx {
         &self.regs
     }
 }
 impl DerefMut for IxgbeTxQueueRegisters {
-    fn deref_mut(&mut self) -> &mut Box<RegistersTx> {
+    fn deref_mut(&mut self) -> &mut RegistersTx {
         &mut self.regs
     }
 }
+
+/// Struct that gives access to the ixgbe registers used to configure Receive Side Scaling (RSS),
+/// which are split across two of the NIC's register blocks.
+pub struct IxgbeRssRegisters<'r> {
+    pub regs2: &'r mut IntelIxgbeRegisters2,
+    pub regs3: &'r mut IntelIxgbeRegisters3,
+}
+impl<'r> RssRegisters for IxgbeRssRegisters<'r> {
+    fn redirection_table_len(&self) -> usize {
+        IXGBE_RSS_REDIRECTION_TABLE_LEN
+    }
+    fn set_redirection_table_entry(&mut self, index: usize, queue: u8) {
+        let reta = &mut self.regs3.reta[index / 4];
+        let shift = (index % 4) * 8;
+        let val = (reta.read() & !(0xFF << shift)) | ((queue as u32 & RETA_ENTRY_QUEUE_MASK) << shift);
+        reta.write(val);
+    }
+    fn hash_key_len(&self) -> usize {
+        IXGBE_RSS_HASH_KEY_LEN
+    }
+    fn set_hash_key(&mut self, key: &[u8]) {
+        for (rssrk, bytes) in self.regs3.rssrk.iter_mut().zip(key.chunks_exact(4)) {
+            rssrk.write(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        }
+    }
+    fn enable_rss(&mut self) {
+        // enable RSS writeback in the header field of the receive descriptor
+        self.regs2.rxcsum.write(RXCSUM_PCSD);
+        // enable RSS and set fields that will be used by hash function
+        // right now we're using the udp port and ipv4 address.
+        self.regs3.mrqc.write(MRQC_MRQE_RSS | MRQC_UDPIPV4);
+    }
+}
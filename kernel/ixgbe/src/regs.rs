@@ -146,7 +146,11 @@ pub struct IntelIxgbeRegisters2 {
 
     /// MAC Core Control 0 Register 
     pub hlreg0:                         Volatile<u32>,          // 0x4240;
-    _padding13:                         [u8; 92],               // 0x4244 - 0x429F
+    _padding13:                         [u8; 36],               // 0x4244 - 0x4267
+
+    /// Max Frame Size Register
+    pub maxfrs:                         Volatile<u32>,          // 0x4268;
+    _padding13a:                        [u8; 52],               // 0x426C - 0x429F
 
     /// Auto-Negotiation Control Register
     pub autoc:                          Volatile<u32>,          // 0x42A0;
@@ -183,11 +187,31 @@ pub struct IntelIxgbeRegisters2 {
 
     /// Filter Control Register
     pub fctrl:                          Volatile<u32>,          // 0x5080;
-    _padding20:                         [u8; 164],              // 0x5084 - 0x5127
+    _padding20:                         [u8; 12],               // 0x5084 - 0x508F
+
+    /// Multicast Control Register
+    pub mcstctrl:                       Volatile<u32>,          // 0x5090;
+    _padding20a:                        [u8; 148],              // 0x5094 - 0x5127
 
     /// EType Queue Filter
     pub etqf:                           [Volatile<u32>;8],      // 0x5128 - 0x5147;
-    _padding21:                         [u8; 3768],             // 0x5148 - 0x5FFF
+    _padding21:                         [u8; 64],               // 0x5148 - 0x5187
+
+    /// Rx Time Sync Control
+    pub tsyncrxctl:                     Volatile<u32>,          // 0x5188
+    _padding22:                         [u8; 24],               // 0x518C - 0x51A3
+
+    /// Rx Timestamp High
+    pub rxstmph:                        Volatile<u32>,          // 0x51A4
+    _padding23:                         [u8; 64],               // 0x51A8 - 0x51E7
+
+    /// Rx Timestamp Low
+    pub rxstmpl:                        Volatile<u32>,          // 0x51E8
+    _padding24:                         [u8; 20],               // 0x51EC - 0x51FF
+
+    /// Multicast Table Array, a 4096-bit hash table of the multicast addresses that are accepted
+    pub mta:                            [Volatile<u32>;128],    // 0x5200 - 0x53FF
+    _padding24a:                        [u8; 3072],             // 0x5400 - 0x5FFF
 } // 4 4KiB page
 
 const_assert_eq!(core::mem::size_of::<IntelIxgbeRegisters2>(), 4 * 4096);
@@ -209,7 +233,15 @@ pub struct IntelIxgbeMacRegisters {
     _padding1:                          [u8; 256],              // 0x8000 - 0x80FF
     /// DMA Tx TCP Max Allow Size Requests
     pub dtxmxszrq:                      Volatile<u32>,          // 0X8100
-    _padding2:                          [u8; 8444],             // 0x8104 - 0xA1FF
+    _padding2:                          [u8; 2824],             // 0x8104 - 0x8C0B
+
+    /// System Time Register Low
+    pub systiml:                        Volatile<u32>,          // 0x8C0C
+    /// System Time Register High
+    pub systimh:                        Volatile<u32>,          // 0x8C10
+    /// Increment Attributes Register
+    pub timinca:                        Volatile<u32>,          // 0x8C14
+    _padding2a:                         [u8; 5608],             // 0x8C18 - 0xA1FF
     
     /// Receive Address Low
     pub ral:                            Volatile<u32>,          // 0xA200;
@@ -386,6 +418,8 @@ pub const TDT_DIST:                     usize = 0x40;
 
 // Link set up commands
 pub const AUTOC_LMS_CLEAR:              u32 = 0x0000_E000; 
+/// Link Mode Select: 1 GbE link without backplane auto-negotiation
+pub const AUTOC_LMS_1_GB_NO_AN:         u32 = 0 << 13;
 pub const AUTOC_LMS_1_GB:               u32 = 0x0000_E000;
 pub const AUTOC_LMS_10_GBE_P:           u32 = 1 << 13;
 pub const AUTOC_LMS_10_GBE_S:           u32 = 3 << 13;
@@ -415,8 +449,31 @@ pub const SW_FW_SYNC_FW_MAC:            u32 = 1 << 8;
 /// Bit which indicates that auto-read by hardware from EEPROM is done
 pub const EEC_AUTO_RD:                  u32 = 9;
 
+// Time Sync Commands
+/// Rx timestamp valid, cleared by reading `rxstmph`
+pub const TSYNCRXCTL_VALID:             u32 = 1 << 0;
+/// Timestamp L2 (V2) and L4 (V1 and V2) PTP event packets
+pub const TSYNCRXCTL_TYPE_L2_L4_V2:     u32 = 2 << 1;
+/// Enable Rx timestamping
+pub const TSYNCRXCTL_EN:                u32 = 1 << 4;
+/// Ethertype of IEEE 1588 (PTP) packets sent directly over Ethernet
+pub const ETHERTYPE_1588:               u32 = 0x88F7;
+/// Packets matching this EType filter are candidates for timestamping
+pub const ETQF_1588:                    u32 = 1 << 30;
+/// Enable this EType filter
+pub const ETQF_FILTER_EN:               u32 = 1 << 31;
+/// The EType filter used to identify L2 PTP packets
+pub const ETQF_FILTER_1588:             usize = 3;
+/// Value of TIMINCA for a 10 Gbps link: increments SYSTIM every 6.4 ns by 0xCCCCCC,
+/// i.e., by 2^21 per nanosecond.
+pub const TIMINCA_10GB:                 u32 = (1 << 24) | 0x00CC_CCCC;
+/// The number of bits to shift a SYSTIM (or Rx timestamp) value right by to convert it to nanoseconds
+pub const SYSTIM_NS_SHIFT:              u32 = 21;
+
 // Link Commands
 pub const LINKS_SPEED_MASK:             u32 = 0x3 << 28;
+/// Bit which indicates that the link is up
+pub const LINKS_UP:                     u32 = 1 << 30;
 
 // MAC Control Commands
 /// Tx CRC Enable by HW (bit 0)
@@ -425,6 +482,10 @@ pub const HLREG0_TXCRCEN:               u32 = 1;
 pub const HLREG0_TXPADEN:               u32 = 1 << 10;
 /// Enable CRC strip by HW
 pub const HLREG0_CRC_STRIP:             u32 = 1 << 1;
+/// Jumbo Frame Enable, allows received frames up to the size in MAXFRS (bit 2)
+pub const HLREG0_JUMBOEN:               u32 = 1 << 2;
+/// The Max Frame Size field occupies the upper 16 bits of MAXFRS
+pub const MAXFRS_MFS_SHIFT:             u32 = 16;
 /// Enable CRC strip by HW
 pub const RDRXCTL_CRC_STRIP:            u32 = 1;
 /// These 5 bits have to be cleared by software
@@ -450,6 +511,16 @@ pub const STORE_BAD_PACKETS:            u32 = 1 << 1;
 pub const MULTICAST_PROMISCUOUS_ENABLE: u32 = 1 << 8;
 pub const UNICAST_PROMISCUOUS_ENABLE:   u32 = 1 << 9;
 pub const BROADCAST_ACCEPT_MODE:        u32 = 1 << 10;
+
+// MCSTCTRL commands
+/// Use bits 47:36 of the destination address to index the Multicast Table Array
+pub const MCSTCTRL_MO_47_36:            u32 = 0;
+/// Multicast Filter Enable
+pub const MCSTCTRL_MFE:                 u32 = 1 << 2;
+/// The number of 32-bit registers in the Multicast Table Array
+pub const IXGBE_NUM_MTA_REGS:           usize = 128;
+/// Address Valid bit in the RAH register
+pub const RAH_AV:                       u32 = 1 << 31;
 pub const RECEIVE_ENABLE:               u32 = 1;
 pub const DROP_ENABLE:                  u32 = 1 << 28;
 pub const DCA_RXCTRL_CLEAR_BIT_12:      u32 = 1 << 12;
@@ -468,6 +539,14 @@ pub const RETA_ENTRY_0_OFFSET:          u32 = 0;
 pub const RETA_ENTRY_1_OFFSET:          u32 = 8;
 pub const RETA_ENTRY_2_OFFSET:          u32 = 16;
 pub const RETA_ENTRY_3_OFFSET:          u32 = 24;
+/// Each redirection table entry is one byte, of which only the lower 4 bits hold the queue index.
+pub const RETA_ENTRY_QUEUE_MASK:        u32 = 0xF;
+/// The number of entries in the RSS redirection table, 4 in each of the 32 RETA registers.
+pub const IXGBE_RSS_REDIRECTION_TABLE_LEN: usize = 128;
+/// The length in bytes of the RSS hash key, stored across the 10 RSSRK registers.
+pub const IXGBE_RSS_HASH_KEY_LEN:       usize = 40;
+/// The maximum number of receive queues that RSS can distribute packets across, due to the 4-bit redirection table entries.
+pub const IXGBE_RSS_MAX_QUEUES:         u8 = 16;
 
 // DCA commands
 pub const RX_DESC_DCA_ENABLE:           u32 = 1 << 5;
@@ -5,12 +5,17 @@ extern crate spin;
 extern crate event_types;
 extern crate ps2;
 extern crate mpmc;
+extern crate irq_safety;
+extern crate alloc;
 #[macro_use] extern crate log;
 
 
 use keycodes_ascii::{Keycode, KeyboardModifiers, KEY_RELEASED_OFFSET, KeyAction, KeyEvent};
+use core::sync::atomic::{AtomicU8, Ordering};
+use alloc::vec::Vec;
 use spin::Once;
 use mpmc::Queue;
+use irq_safety::MutexIrqSafe;
 use event_types::Event;
 use ps2::{init_ps2_port1,test_ps2_port1,keyboard_led,keyboard_detect,KeyboardType};
 
@@ -22,11 +27,24 @@ static mut KBD_MODIFIERS: KeyboardModifiers = KeyboardModifiers::new();
 static KEYBOARD_PRODUCER: Once<Queue<Event>> = Once::new();
 
 /// Bitmask for the Scroll Lock keyboard LED
-const SCROLL_LED: u8 = 0b001;
+pub const SCROLL_LED: u8 = 0b001;
 /// Bitmask for the Num Lock keyboard LED
-const NUM_LED: u8 = 0b010;
+pub const NUM_LED: u8 = 0b010;
 /// Bitmask for the Caps Lock keyboard LED
-const CAPS_LED: u8 = 0b100;
+pub const CAPS_LED: u8 = 0b100;
+
+/// A function that applies the given LED bitmask to one physical keyboard.
+///
+/// The bitmask uses the `SCROLL_LED`, `NUM_LED`, and `CAPS_LED` bits defined above,
+/// which is the same layout that the PS/2 "set LEDs" command uses.
+pub type KeyboardLedSink = fn(led_bitmask: u8);
+
+/// The current state of the lock-key LEDs, shared by every attached keyboard.
+/// This is the single source of truth; each registered sink merely mirrors it.
+static LED_STATE: AtomicU8 = AtomicU8::new(0);
+
+/// The additional keyboards (beyond the PS/2 keyboard) whose LEDs should be kept in sync.
+static LED_SINKS: MutexIrqSafe<Vec<KeyboardLedSink>> = MutexIrqSafe::new(Vec::new());
 
 /// Initialize the keyboard driver. 
 /// Arguments: a reference to a queue onto which keyboard events should be enqueued. 
@@ -139,6 +157,34 @@ pub fn handle_keyboard_input(scan_code: u8, extended: bool) -> Result<(), &'stat
 }
 
 
+
+/// Registers a sink that will be notified whenever the lock-key LED state changes,
+/// e.g., the LED output report of another keyboard device.
+///
+/// The sink is immediately invoked with the current LED state
+/// such that a newly-attached keyboard starts out in sync with the others.
+pub fn register_led_sink(sink: KeyboardLedSink) {
+    LED_SINKS.lock().push(sink);
+    sink(LED_STATE.load(Ordering::SeqCst));
+}
+
+/// Removes a sink that was previously registered via [`register_led_sink()`],
+/// e.g., when its keyboard device is detached.
+///
+/// Returns `true` if the sink was found and removed.
+pub fn unregister_led_sink(sink: KeyboardLedSink) -> bool {
+    let mut sinks = LED_SINKS.lock();
+    let len_before = sinks.len();
+    sinks.retain(|s| *s as usize != sink as usize);
+    sinks.len() != len_before
+}
+
+/// Returns the current lock-key LED bitmask, see [`KeyboardLedSink`] for its layout.
+pub fn led_state() -> u8 {
+    LED_STATE.load(Ordering::SeqCst)
+}
+
+
 fn set_keyboard_led(modifiers: &KeyboardModifiers) {
     let mut led_bitmask: u8 = 0; 
     if modifiers.is_caps_lock() {
@@ -151,5 +197,12 @@ fn set_keyboard_led(modifiers: &KeyboardModifiers) {
         led_bitmask |= SCROLL_LED;
     }
 
+    LED_STATE.store(led_bitmask, Ordering::SeqCst);
     keyboard_led(led_bitmask);
-}
\ No newline at end of file
+    // Don't hold the lock while running the sinks, in case they (un)register sinks
+    // or take a while to send the LED state to their keyboard.
+    let sinks = LED_SINKS.lock().clone();
+    for sink in sinks {
+        sink(led_bitmask);
+    }
+}
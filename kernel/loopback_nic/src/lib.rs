@@ -0,0 +1,172 @@
+//! A purely software loopback NIC, which delivers every frame it transmits back to its own receive queue.
+//! 
+//! This allows the network stack, the smoltcp adapter (`ethernet_smoltcp_device`), 
+//! and the packet capture tap to be exercised without any NIC hardware or emulated NIC model.
+//! For example, an interface using the loopback NIC can be created with:
+//! ```ignore
+//! let nic = loopback_nic::init()?;
+//! let iface = EthernetNetworkInterface::new_ipv4_interface(nic, "127.0.0.1/8", &[127, 0, 0, 1])?;
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+#[macro_use] extern crate lazy_static;
+extern crate spin;
+extern crate mpmc;
+extern crate irq_safety;
+extern crate memory;
+extern crate network_interface_card;
+extern crate nic_buffers;
+extern crate packet_capture;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use alloc::{
+    vec,
+    collections::VecDeque,
+};
+use spin::Once;
+use irq_safety::MutexIrqSafe;
+use memory::{EntryFlags, PAGE_SIZE, create_contiguous_mapping};
+use network_interface_card::{NetworkInterfaceCard, ReceiveFilter};
+use nic_buffers::{TransmitBuffer, ReceiveBuffer, ReceivedFrame};
+use packet_capture::Direction;
+
+/// The locally-administered MAC address of the loopback NIC.
+pub const LOOPBACK_MAC_ADDRESS: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+/// The maximum number of received frames that can be pending before further frames are dropped.
+const LOOPBACK_MAX_PENDING_FRAMES: usize = 64;
+
+/// The size of each receive buffer, which bounds the size of a frame that can be looped back.
+const LOOPBACK_RX_BUFFER_SIZE_IN_BYTES: u16 = PAGE_SIZE as u16;
+
+/// The maximum number of ReceiveBuffers that will ever be allocated for the loopback NIC.
+/// Every pending frame holds one buffer, and higher layers may hold on to a few more while consuming frames.
+const RX_BUFFER_POOL_SIZE: usize = LOOPBACK_MAX_PENDING_FRAMES * 2;
+lazy_static! {
+    /// The pool of receive buffers that frames are copied into when they are looped back.
+    /// 
+    /// The queue's capacity must exceed the number of buffers it may hold,
+    /// so it is twice the number of buffers we ever allocate.
+    static ref RX_BUFFER_POOL: mpmc::Queue<ReceiveBuffer> = mpmc::Queue::with_capacity(RX_BUFFER_POOL_SIZE * 2);
+}
+
+/// The number of ReceiveBuffers that have been allocated so far, which never exceeds `RX_BUFFER_POOL_SIZE`.
+static ALLOCATED_RX_BUFFERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The single instance of the loopback NIC.
+static LOOPBACK_NIC: Once<MutexIrqSafe<LoopbackNic>> = Once::new();
+
+/// Initializes the loopback NIC, if it wasn't already initialized, and returns a reference to it.
+pub fn init() -> Result<&'static MutexIrqSafe<LoopbackNic>, &'static str> {
+    Ok(LOOPBACK_NIC.call_once(|| MutexIrqSafe::new(LoopbackNic {
+        received_frames: VecDeque::new(),
+        dropped_frames: 0,
+    })))
+}
+
+/// Returns a reference to the loopback NIC, if it has been initialized.
+pub fn get_loopback_nic() -> Option<&'static MutexIrqSafe<LoopbackNic>> {
+    LOOPBACK_NIC.get()
+}
+
+
+/// A software NIC whose transmitted frames are immediately received by itself.
+pub struct LoopbackNic {
+    /// Frames that have been sent but not yet consumed by a higher layer.
+    received_frames: VecDeque<ReceivedFrame>,
+    /// The number of frames dropped because too many frames were pending
+    /// or no receive buffer was available.
+    dropped_frames: usize,
+}
+
+impl LoopbackNic {
+    /// Returns the number of frames dropped because too many frames were pending
+    /// or no receive buffer was available.
+    pub fn dropped_frames(&self) -> usize {
+        self.dropped_frames
+    }
+
+    /// Obtains a receive buffer, reusing one from the pool if possible.
+    /// 
+    /// Returns `Ok(None)` if the pool is empty and the maximum number of buffers
+    /// has already been allocated.
+    fn get_receive_buffer() -> Result<Option<ReceiveBuffer>, &'static str> {
+        if let Some(rx_buf) = RX_BUFFER_POOL.pop() {
+            return Ok(Some(rx_buf));
+        }
+        let reserved = ALLOCATED_RX_BUFFERS.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |allocated|
+            if allocated < RX_BUFFER_POOL_SIZE { Some(allocated + 1) } else { None }
+        );
+        if reserved.is_err() {
+            return Ok(None);
+        }
+        let (mp, phys_addr) = create_contiguous_mapping(
+            LOOPBACK_RX_BUFFER_SIZE_IN_BYTES as usize,
+            EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
+        ).map_err(|e| {
+            ALLOCATED_RX_BUFFERS.fetch_sub(1, Ordering::SeqCst);
+            e
+        })?;
+        Ok(Some(ReceiveBuffer::new(mp, phys_addr, 0, &RX_BUFFER_POOL)))
+    }
+}
+
+impl NetworkInterfaceCard for LoopbackNic {
+    fn send_packet(&mut self, transmit_buffer: TransmitBuffer) -> Result<(), &'static str> {
+        let length = transmit_buffer.length;
+        if length > LOOPBACK_RX_BUFFER_SIZE_IN_BYTES {
+            return Err("loopback_nic: frame is larger than the receive buffer size");
+        }
+        let bytes = transmit_buffer.as_slice::<u8>(0, length as usize)?;
+        packet_capture::capture(Direction::Transmit, 0, core::iter::once(bytes));
+
+        if self.received_frames.len() >= LOOPBACK_MAX_PENDING_FRAMES {
+            self.dropped_frames += 1;
+            return Ok(());
+        }
+
+        let mut rx_buf = match Self::get_receive_buffer()? {
+            Some(rx_buf) => rx_buf,
+            None => {
+                self.dropped_frames += 1;
+                return Ok(());
+            }
+        };
+        rx_buf.as_slice_mut::<u8>(0, length as usize)?.copy_from_slice(bytes);
+        rx_buf.length = length;
+        packet_capture::capture(Direction::Receive, 0, core::iter::once(bytes));
+
+        self.received_frames.push_back(ReceivedFrame(vec![rx_buf], None));
+        Ok(())
+    }
+
+    fn get_received_frame(&mut self) -> Option<ReceivedFrame> {
+        self.received_frames.pop_front()
+    }
+
+    fn poll_receive(&mut self) -> Result<(), &'static str> {
+        // Frames are received as soon as they are sent, so there's nothing to poll.
+        Ok(())
+    }
+
+    fn mac_address(&self) -> [u8; 6] {
+        LOOPBACK_MAC_ADDRESS
+    }
+}
+
+// The loopback NIC delivers every frame it sends, so it has no filters to program.
+impl ReceiveFilter for LoopbackNic {
+    fn set_unicast_address(&mut self, _mac_address: [u8; 6]) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn set_multicast_addresses(&mut self, _multicast_addresses: &[[u8; 6]]) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn set_promiscuous(&mut self, _enable: bool) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
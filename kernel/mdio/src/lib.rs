@@ -0,0 +1,178 @@
+//! Access to Ethernet PHYs through the MDIO (Management Data Input/Output) bus.
+//! 
+//! The registers of a PHY are accessed through an [`MdioBus`], which is implemented by each NIC
+//! for its own management interface, e.g., the MDI control register ([`IntelMdic`]) of Intel NICs.
+//! A PHY can be on-chip (internal) or an external device on the same MDIO bus,
+//! so it is identified by its address on that bus.
+//! 
+//! The [`Phy`] type builds on top of an `MdioBus` to provide common link management operations
+//! using the standard IEEE 802.3 clause 22 registers defined in the [`regs`] module.
+
+#![no_std]
+
+extern crate volatile;
+extern crate network_interface_card;
+
+pub mod regs;
+
+use volatile::Volatile;
+use network_interface_card::{LinkSpeed, Duplex, LinkAbilities};
+use regs::*;
+
+
+/// A management bus over which the registers of one or more PHYs can be read and written.
+pub trait MdioBus {
+    /// Reads the 16-bit PHY register `reg_addr` of the PHY at `phy_addr` on this bus.
+    fn read(&mut self, phy_addr: u8, reg_addr: u8) -> Result<u16, &'static str>;
+
+    /// Writes `value` to the 16-bit PHY register `reg_addr` of the PHY at `phy_addr` on this bus.
+    fn write(&mut self, phy_addr: u8, reg_addr: u8, value: u16) -> Result<(), &'static str>;
+}
+
+
+/// An `MdioBus` implemented by the MDI Control register (MDIC) found in Intel e1000-class NICs.
+pub struct IntelMdic<'r>(pub &'r mut Volatile<u32>);
+
+// MDIC fields
+const MDIC_DATA_MASK:           u32 = 0xFFFF;
+const MDIC_REGADD_SHIFT:        u32 = 16;
+const MDIC_PHYADD_SHIFT:        u32 = 21;
+const MDIC_OP_WRITE:            u32 = 1 << 26;
+const MDIC_OP_READ:             u32 = 2 << 26;
+/// Set by hardware when the MDI transaction has completed
+const MDIC_READY:               u32 = 1 << 28;
+/// Set by hardware if the MDI read transaction failed
+const MDIC_ERROR:               u32 = 1 << 30;
+
+/// An MDI transaction takes tens of microseconds, so this bound is very generous.
+const MDIC_POLL_ATTEMPTS:       usize = 100_000;
+
+impl<'r> IntelMdic<'r> {
+    /// Polls the MDIC register until the current transaction completes,
+    /// and returns the final value of the register.
+    fn wait_for_completion(&self) -> Result<u32, &'static str> {
+        for _ in 0..MDIC_POLL_ATTEMPTS {
+            let mdic = self.0.read();
+            if mdic & MDIC_READY == MDIC_READY {
+                return Ok(mdic);
+            }
+            core::hint::spin_loop();
+        }
+        Err("mdio: timed out waiting for MDI transaction to complete")
+    }
+
+    fn command(phy_addr: u8, reg_addr: u8) -> u32 {
+        ((reg_addr as u32 & 0x1F) << MDIC_REGADD_SHIFT) | ((phy_addr as u32 & 0x1F) << MDIC_PHYADD_SHIFT)
+    }
+}
+
+impl<'r> MdioBus for IntelMdic<'r> {
+    fn read(&mut self, phy_addr: u8, reg_addr: u8) -> Result<u16, &'static str> {
+        self.0.write(Self::command(phy_addr, reg_addr) | MDIC_OP_READ);
+        let mdic = self.wait_for_completion()?;
+        if mdic & MDIC_ERROR == MDIC_ERROR {
+            return Err("mdio: MDI read of PHY register failed");
+        }
+        Ok((mdic & MDIC_DATA_MASK) as u16)
+    }
+
+    fn write(&mut self, phy_addr: u8, reg_addr: u8, value: u16) -> Result<(), &'static str> {
+        self.0.write(Self::command(phy_addr, reg_addr) | MDIC_OP_WRITE | value as u32);
+        self.wait_for_completion().map(|_| ())
+    }
+}
+
+
+/// A PHY at a given address on an [`MdioBus`].
+pub struct Phy<B: MdioBus> {
+    bus: B,
+    addr: u8,
+}
+
+impl<B: MdioBus> Phy<B> {
+    /// Creates a handle to the PHY at address `addr` on the given MDIO `bus`.
+    pub fn new(bus: B, addr: u8) -> Phy<B> {
+        Phy { bus, addr }
+    }
+
+    /// Reads the PHY register `reg_addr`.
+    pub fn read(&mut self, reg_addr: u8) -> Result<u16, &'static str> {
+        self.bus.read(self.addr, reg_addr)
+    }
+
+    /// Writes `value` to the PHY register `reg_addr`.
+    pub fn write(&mut self, reg_addr: u8, value: u16) -> Result<(), &'static str> {
+        self.bus.write(self.addr, reg_addr, value)
+    }
+
+    /// Returns the 32-bit PHY identifier, made up of the OUI, model number, and revision number.
+    pub fn id(&mut self) -> Result<u32, &'static str> {
+        let id1 = self.read(PHY_ID1)? as u32;
+        let id2 = self.read(PHY_ID2)? as u32;
+        Ok((id1 << 16) | id2)
+    }
+
+    /// Initiates a software reset of the PHY. 
+    /// The reset bit is self-clearing once the PHY has finished resetting.
+    pub fn reset(&mut self) -> Result<(), &'static str> {
+        let ctrl = self.read(PHY_CTRL)?;
+        self.write(PHY_CTRL, ctrl | PHY_CTRL_RESET)
+    }
+
+    /// Returns whether the PHY currently reports that the link is up.
+    pub fn link_up(&mut self) -> Result<bool, &'static str> {
+        // The link status bit is latched low, so read it twice to obtain the current status.
+        let _ = self.read(PHY_STATUS)?;
+        Ok(self.read(PHY_STATUS)? & PHY_STATUS_LINK_UP != 0)
+    }
+
+    /// Disables auto-negotiation and forces the PHY to the given `speed` and `duplex` mode.
+    /// 
+    /// Only 10 and 100 Mbps can be forced; 1000BASE-T requires auto-negotiation.
+    pub fn force(&mut self, speed: LinkSpeed, duplex: Duplex) -> Result<(), &'static str> {
+        let speed_bits = match speed {
+            LinkSpeed::Mbps10  => 0,
+            LinkSpeed::Mbps100 => PHY_CTRL_SPEED_LSB,
+            _ => return Err("mdio: only 10 and 100 Mbps links can be forced, higher speeds require auto-negotiation"),
+        };
+        let duplex_bits = match duplex {
+            Duplex::Full => PHY_CTRL_FULL_DUPLEX,
+            Duplex::Half => 0,
+        };
+        self.write(PHY_CTRL, speed_bits | duplex_bits)
+    }
+
+    /// Re-enables auto-negotiation (if it was disabled) and restarts it.
+    pub fn restart_autonegotiation(&mut self) -> Result<(), &'static str> {
+        let ctrl = self.read(PHY_CTRL)?;
+        self.write(PHY_CTRL, ctrl | PHY_CTRL_AN_ENABLE | PHY_CTRL_RESTART_AN)
+    }
+
+    /// Returns the abilities that the link partner advertised during the last auto-negotiation.
+    /// 
+    /// Returns an error if auto-negotiation hasn't completed yet.
+    pub fn link_partner_abilities(&mut self) -> Result<LinkAbilities, &'static str> {
+        let status = self.read(PHY_STATUS)?;
+        if status & PHY_STATUS_AN_COMPLETE == 0 {
+            return Err("mdio: auto-negotiation has not completed");
+        }
+        let lp = self.read(PHY_LP_ABILITY)?;
+        // Only PHYs that support extended status (i.e., gigabit PHYs) have the 1000BASE-T status register.
+        let lp_1000t = if status & PHY_STATUS_EXTENDED_STATUS != 0 {
+            self.read(PHY_1000T_STATUS)?
+        } else {
+            0
+        };
+
+        Ok(LinkAbilities {
+            half_duplex_10:    lp & PHY_LP_10T_HD != 0,
+            full_duplex_10:    lp & PHY_LP_10T_FD != 0,
+            half_duplex_100:   lp & PHY_LP_100TX_HD != 0,
+            full_duplex_100:   lp & PHY_LP_100TX_FD != 0,
+            half_duplex_1000:  lp_1000t & PHY_1000T_LP_HD != 0,
+            full_duplex_1000:  lp_1000t & PHY_1000T_LP_FD != 0,
+            full_duplex_10000: false,
+            pause:             lp & PHY_LP_PAUSE != 0,
+        })
+    }
+}
@@ -0,0 +1,64 @@
+//! Standard PHY (MII) registers and their bits, as defined in IEEE 802.3 clause 22.
+//! 
+//! Vendor-specific registers (addresses 16 through 31) are not defined here.
+
+/// PHY Control Register
+pub const PHY_CTRL:                     u8 = 0;
+/// PHY Status Register
+pub const PHY_STATUS:                   u8 = 1;
+/// PHY Identifier Register 1 (OUI bits 3:18)
+pub const PHY_ID1:                      u8 = 2;
+/// PHY Identifier Register 2 (OUI bits 19:24, model number, revision)
+pub const PHY_ID2:                      u8 = 3;
+/// Auto-Negotiation Advertisement Register
+pub const PHY_AN_ADVERTISEMENT:         u8 = 4;
+/// Auto-Negotiation Link Partner Ability Register
+pub const PHY_LP_ABILITY:               u8 = 5;
+/// Auto-Negotiation Expansion Register
+pub const PHY_AN_EXPANSION:             u8 = 6;
+/// 1000BASE-T Control Register
+pub const PHY_1000T_CTRL:               u8 = 9;
+/// 1000BASE-T Status Register
+pub const PHY_1000T_STATUS:             u8 = 10;
+/// Extended Status Register
+pub const PHY_EXTENDED_STATUS:          u8 = 15;
+
+// PHY Control Register bits
+/// Speed selection (MSB), used together with `PHY_CTRL_SPEED_LSB`
+pub const PHY_CTRL_SPEED_MSB:           u16 = 1 << 6;
+/// Full-Duplex
+pub const PHY_CTRL_FULL_DUPLEX:         u16 = 1 << 8;
+/// Restart Auto-Negotiation
+pub const PHY_CTRL_RESTART_AN:          u16 = 1 << 9;
+/// Isolate the PHY from the MII
+pub const PHY_CTRL_ISOLATE:             u16 = 1 << 10;
+/// Power Down
+pub const PHY_CTRL_POWER_DOWN:          u16 = 1 << 11;
+/// Auto-Negotiation Enable
+pub const PHY_CTRL_AN_ENABLE:           u16 = 1 << 12;
+/// Speed selection (LSB), used together with `PHY_CTRL_SPEED_MSB`
+pub const PHY_CTRL_SPEED_LSB:           u16 = 1 << 13;
+/// Loopback
+pub const PHY_CTRL_LOOPBACK:            u16 = 1 << 14;
+/// Software Reset
+pub const PHY_CTRL_RESET:               u16 = 1 << 15;
+
+// PHY Status Register bits
+/// Link Status (latched low)
+pub const PHY_STATUS_LINK_UP:           u16 = 1 << 2;
+/// Auto-Negotiation Complete
+pub const PHY_STATUS_AN_COMPLETE:       u16 = 1 << 5;
+/// Extended status information is available in `PHY_EXTENDED_STATUS`
+pub const PHY_STATUS_EXTENDED_STATUS:   u16 = 1 << 8;
+
+// Link Partner Ability Register bits (these match the Advertisement Register bits)
+pub const PHY_LP_10T_HD:                u16 = 1 << 5;
+pub const PHY_LP_10T_FD:                u16 = 1 << 6;
+pub const PHY_LP_100TX_HD:              u16 = 1 << 7;
+pub const PHY_LP_100TX_FD:              u16 = 1 << 8;
+pub const PHY_LP_PAUSE:                 u16 = 1 << 10;
+pub const PHY_LP_ASYMMETRIC_PAUSE:      u16 = 1 << 11;
+
+// 1000BASE-T Status Register bits
+pub const PHY_1000T_LP_HD:              u16 = 1 << 10;
+pub const PHY_1000T_LP_FD:              u16 = 1 << 11;
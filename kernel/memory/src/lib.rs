@@ -32,12 +32,15 @@ mod paging;
 #[cfg(mapper_spillful)]
 pub mod paging;
 
+mod low_memory;
+
 
 pub use self::paging::*;
 
 pub use memory_structs::*;
 pub use page_allocator::*;
 pub use frame_allocator::*;
+pub use low_memory::{LowMemoryLimit, LowMemoryFrames, LowMemoryMapping, allocate_low_frames, create_low_memory_mapping};
 
 #[cfg(target_arch = "x86_64")]
 use memory_x86_64::*;
@@ -115,6 +118,34 @@ pub fn create_mapping(size_in_bytes: usize, flags: EntryFlags) -> Result<MappedP
 }
 
 
+/// A convenience function that creates a new virtually-contiguous memory mapping backed by physically-scattered frames,
+/// which avoids competing for large contiguous runs of physical memory. 
+/// This is intended for large buffers used by devices that support scatter-gather DMA,
+/// which can be given the physical address of each page separately. 
+/// If contiguous frames are required, then see [`create_contiguous_mapping()`](fn.create_contiguous_mapping.html).
+/// 
+/// Returns a tuple containing the new `MappedPages` and the starting `PhysicalAddress` of the frame 
+/// backing each of its pages, in order. 
+/// 
+/// # Locking / Deadlock
+/// Currently, this function acquires the lock on the kernel's `MemoryManagementInfo` instance.
+/// Thus, the caller should ensure that lock is not held when invoking this function.
+pub fn create_scattered_mapping(size_in_bytes: usize, flags: EntryFlags) -> Result<(MappedPages, Vec<PhysicalAddress>), &'static str> {
+    let allocated_pages = allocate_pages_by_bytes(size_in_bytes).ok_or("memory::create_scattered_mapping(): couldn't allocate pages!")?;
+    let kernel_mmi_ref = get_kernel_mmi_ref().ok_or("create_scattered_mapping(): KERNEL_MMI was not yet initialized!")?;
+    let mut kernel_mmi = kernel_mmi_ref.lock();
+    let mp = kernel_mmi.page_table.map_allocated_pages(allocated_pages, flags)?;
+
+    let mut frame_addresses = Vec::with_capacity(mp.size_in_pages());
+    for page in (*mp).clone() {
+        let frame = kernel_mmi.page_table.translate_page(page)
+            .ok_or("BUG: create_scattered_mapping(): newly-mapped page was not mapped to a frame")?;
+        frame_addresses.push(frame.start_address());
+    }
+    Ok((mp, frame_addresses))
+}
+
+
 pub static BROADCAST_TLB_SHOOTDOWN_FUNC: Once<fn(PageRange)> = Once::new();
 
 /// Set the function callback that will be invoked every time a TLB shootdown is necessary,
@@ -206,6 +237,8 @@ pub fn init(
 
     frame_allocator::init(free_regions.iter().flatten(), reserved_regions.iter().flatten())?;
     debug!("Initialized new frame allocator!");
+    // Reserve low memory for legacy devices before any other frames can be allocated.
+    low_memory::init(boot_info);
     frame_allocator::dump_frame_allocator_state();
 
     page_allocator::init(VirtualAddress::new_canonical(kernel_phys_end.value()))?;
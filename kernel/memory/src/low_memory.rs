@@ -0,0 +1,230 @@
+//! A manager of reserved low physical memory for legacy devices,
+//! e.g., ISA-style DMA controllers, option ROM shadows, and debug structures
+//! that can only address memory below 1 MiB or 16 MiB.
+//! 
+//! A pool of frames in each of those regions is reserved while the memory subsystem is initialized,
+//! before any general frame allocation occurs, such that general allocations cannot consume 
+//! the scarce low memory that these legacy devices require. 
+//! Frames allocated from these pools are returned to them (not to the general frame allocator) when dropped. 
+
+use core::ops::{Deref, DerefMut};
+use alloc::vec::Vec;
+use irq_safety::MutexIrqSafe;
+use multiboot2::{BootInformation, MemoryAreaType};
+use super::{
+    AllocatedFrames, MappedPages, PhysicalAddress, EntryFlags, Mapper,
+    allocate_frames_at, allocate_pages, get_kernel_mmi_ref, PAGE_SIZE,
+};
+
+/// The start of the window of memory below 1 MiB that is reserved for legacy devices.
+/// Memory below this address is used for the real-mode IVT, BIOS data area, and AP startup code.
+const LOW_1MIB_POOL_START: usize = 0x2_0000;
+/// The end (exclusive) of the window of memory below 1 MiB that is reserved for legacy devices.
+/// Memory above this address may be used by the Extended BIOS Data Area.
+const LOW_1MIB_POOL_END: usize = 0x8_0000;
+/// The size of the pool of memory below 16 MiB that is reserved for legacy devices.
+const LOW_16MIB_POOL_SIZE_IN_BYTES: usize = 0x10_0000;
+/// The end (exclusive) of the memory that can be addressed by ISA DMA controllers.
+const LOW_16MIB_POOL_END: usize = 0x100_0000;
+
+/// The physical address limit that memory allocated for a legacy device must fall below.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LowMemoryLimit {
+    /// Memory below 1 MiB, e.g., for real-mode structures or option ROM shadows.
+    Below1MiB = 0,
+    /// Memory below 16 MiB, e.g., for ISA DMA.
+    Below16MiB = 1,
+}
+
+/// A pool of reserved low memory frames.
+struct LowMemoryPool {
+    /// The frames reserved during early memory initialization, before the heap was available.
+    initial_frames: Option<AllocatedFrames>,
+    /// The chunks of frames in this pool that are currently free.
+    free_chunks: Vec<AllocatedFrames>,
+}
+
+static LOW_MEMORY_POOLS: MutexIrqSafe<[LowMemoryPool; 2]> = MutexIrqSafe::new([
+    LowMemoryPool { initial_frames: None, free_chunks: Vec::new() },
+    LowMemoryPool { initial_frames: None, free_chunks: Vec::new() },
+]);
+
+
+/// Reserves the pools of low memory frames.
+/// 
+/// This must be invoked right after the frame allocator is initialized, 
+/// before any other frames are allocated. It does not require the heap.
+pub(crate) fn init(boot_info: &BootInformation) {
+    let mut pools = LOW_MEMORY_POOLS.lock();
+
+    // Memory below 1 MiB is entirely reserved by Theseus, so we only need to ensure that 
+    // the bootloader reported our chosen window as actual usable RAM.
+    let window_is_ram = boot_info.memory_map_tag().map_or(false, |tag| tag.all_memory_areas().any(|area|
+        area.typ() == MemoryAreaType::Available
+            && area.start_address() as usize <= LOW_1MIB_POOL_START
+            && (area.start_address() + area.size()) as usize >= LOW_1MIB_POOL_END
+    ));
+    if window_is_ram {
+        let num_frames = (LOW_1MIB_POOL_END - LOW_1MIB_POOL_START) / PAGE_SIZE;
+        match allocate_frames_at(PhysicalAddress::new_canonical(LOW_1MIB_POOL_START), num_frames) {
+            Ok(frames) => pools[LowMemoryLimit::Below1MiB as usize].initial_frames = Some(frames),
+            Err(e) => warn!("low_memory: couldn't reserve frames below 1 MiB: {}", e),
+        }
+    } else {
+        warn!("low_memory: the region p{:#X} to p{:#X} is not usable RAM, so no memory below 1 MiB is available for legacy devices",
+            LOW_1MIB_POOL_START, LOW_1MIB_POOL_END
+        );
+    }
+
+    // Memory between 1 MiB and 16 MiB is general-purpose, but it's also where the kernel and bootloader modules are loaded.
+    // Thus, we search downwards from 16 MiB for the highest pool-sized region that is still free.
+    let num_frames = LOW_16MIB_POOL_SIZE_IN_BYTES / PAGE_SIZE;
+    let mut start = LOW_16MIB_POOL_END - LOW_16MIB_POOL_SIZE_IN_BYTES;
+    while start >= LOW_1MIB_POOL_END {
+        if let Ok(frames) = allocate_frames_at(PhysicalAddress::new_canonical(start), num_frames) {
+            pools[LowMemoryLimit::Below16MiB as usize].initial_frames = Some(frames);
+            break;
+        }
+        start -= LOW_16MIB_POOL_SIZE_IN_BYTES;
+    }
+
+    for (pool, limit) in pools.iter().zip([LowMemoryLimit::Below1MiB, LowMemoryLimit::Below16MiB].iter()) {
+        match pool.initial_frames {
+            Some(ref frames) => debug!("low_memory: reserved {:?} for legacy devices ({:?})", frames, limit),
+            None => warn!("low_memory: no memory was reserved for legacy devices ({:?})", limit),
+        }
+    }
+}
+
+
+/// Allocates the given number of physically-contiguous frames from the reserved pool of low memory
+/// that lies below the given `limit`.
+/// 
+/// When the returned `LowMemoryFrames` is dropped, its frames are returned to that pool.
+pub fn allocate_low_frames(num_frames: usize, limit: LowMemoryLimit) -> Result<LowMemoryFrames, &'static str> {
+    if num_frames == 0 {
+        return Err("allocate_low_frames(): cannot allocate zero frames");
+    }
+    let mut pools = LOW_MEMORY_POOLS.lock();
+    let pool = &mut pools[limit as usize];
+    if let Some(initial) = pool.initial_frames.take() {
+        pool.free_chunks.push(initial);
+    }
+
+    let index = pool.free_chunks.iter()
+        .position(|chunk| chunk.size_in_frames() >= num_frames)
+        .ok_or("allocate_low_frames(): not enough free low memory in the pool below the given limit")?;
+    let chunk = pool.free_chunks.swap_remove(index);
+    let split_frame = *chunk.start() + num_frames;
+    let (frames, remaining) = chunk.split(split_frame)
+        .map_err(|_| "BUG: allocate_low_frames(): failed to split a chunk of low memory frames")?;
+    if remaining.size_in_frames() > 0 {
+        pool.free_chunks.push(remaining);
+    }
+
+    Ok(LowMemoryFrames { frames: Some(frames), limit })
+}
+
+/// Allocates physically-contiguous frames from the reserved pool of low memory below the given `limit`
+/// and maps them with the given `flags`.
+/// 
+/// This is like [`create_contiguous_mapping()`](../fn.create_contiguous_mapping.html), 
+/// but the frames are returned to the low memory pool when the returned `LowMemoryMapping` is dropped.
+/// 
+/// Returns a tuple containing the new `LowMemoryMapping` and the starting `PhysicalAddress` of its first frame.
+pub fn create_low_memory_mapping(size_in_bytes: usize, limit: LowMemoryLimit, flags: EntryFlags) -> Result<(LowMemoryMapping, PhysicalAddress), &'static str> {
+    let num_frames = (size_in_bytes + PAGE_SIZE - 1) / PAGE_SIZE;
+    let mut low_frames = allocate_low_frames(num_frames, limit)?;
+    let pages = allocate_pages(num_frames).ok_or("create_low_memory_mapping(): couldn't allocate pages!")?;
+
+    let kernel_mmi_ref = get_kernel_mmi_ref().ok_or("create_low_memory_mapping(): KERNEL_MMI was not yet initialized!")?;
+    let frames = low_frames.frames.take().ok_or("BUG: create_low_memory_mapping(): LowMemoryFrames were empty")?;
+    let starting_phys_addr = frames.start_address();
+    // Note: if mapping fails, the frames are consumed and returned to the general frame allocator instead of the pool.
+    let mp = kernel_mmi_ref.lock().page_table.map_allocated_pages_to(pages, frames, flags)?;
+    Ok((LowMemoryMapping { mapped_pages: Some(mp), limit }, starting_phys_addr))
+}
+
+/// Returns the given `frames` to the pool of low memory below the given `limit`,
+/// merging them with any adjacent free chunks.
+fn free_low_frames(mut frames: AllocatedFrames, limit: LowMemoryLimit) {
+    if frames.size_in_frames() == 0 {
+        return;
+    }
+    let mut pools = LOW_MEMORY_POOLS.lock();
+    let pool = &mut pools[limit as usize];
+    while let Some(index) = pool.free_chunks.iter().position(|chunk| 
+        *chunk.end() + 1 == *frames.start() || *frames.end() + 1 == *chunk.start()
+    ) {
+        let adjacent = pool.free_chunks.swap_remove(index);
+        if let Err(adjacent) = frames.merge(adjacent) {
+            pool.free_chunks.push(adjacent);
+            break;
+        }
+    }
+    pool.free_chunks.push(frames);
+}
+
+
+/// Physically-contiguous frames allocated from a reserved pool of low memory, 
+/// obtained from [`allocate_low_frames()`].
+/// 
+/// The frames are returned to that pool when this is dropped.
+#[derive(Debug)]
+pub struct LowMemoryFrames {
+    frames: Option<AllocatedFrames>,
+    limit: LowMemoryLimit,
+}
+
+impl Deref for LowMemoryFrames {
+    type Target = AllocatedFrames;
+    fn deref(&self) -> &AllocatedFrames {
+        // `frames` is only `None` while being consumed by `create_low_memory_mapping()` or dropped.
+        self.frames.as_ref().expect("BUG: LowMemoryFrames were empty")
+    }
+}
+
+impl Drop for LowMemoryFrames {
+    fn drop(&mut self) {
+        if let Some(frames) = self.frames.take() {
+            free_low_frames(frames, self.limit);
+        }
+    }
+}
+
+
+/// A mapping of frames allocated from a reserved pool of low memory, 
+/// obtained from [`create_low_memory_mapping()`].
+/// 
+/// This dereferences to the underlying `MappedPages`. 
+/// When dropped, the pages are unmapped and the frames are returned to the low memory pool.
+#[derive(Debug)]
+pub struct LowMemoryMapping {
+    mapped_pages: Option<MappedPages>,
+    limit: LowMemoryLimit,
+}
+
+impl Deref for LowMemoryMapping {
+    type Target = MappedPages;
+    fn deref(&self) -> &MappedPages {
+        self.mapped_pages.as_ref().expect("BUG: LowMemoryMapping was empty")
+    }
+}
+
+impl DerefMut for LowMemoryMapping {
+    fn deref_mut(&mut self) -> &mut MappedPages {
+        self.mapped_pages.as_mut().expect("BUG: LowMemoryMapping was empty")
+    }
+}
+
+impl Drop for LowMemoryMapping {
+    fn drop(&mut self) {
+        if let Some(mp) = self.mapped_pages.take() {
+            match mp.unmap_into_parts(&mut Mapper::from_current()) {
+                Ok((_pages, Some(frames))) => free_low_frames(frames, self.limit),
+                Ok((_pages, None)) => { }
+                Err(_mp) => error!("LowMemoryMapping::drop(): failed to unmap low memory, its frames will not be returned to the pool"),
+            }
+        }
+    }
+}
@@ -17,26 +17,23 @@ extern crate spin;
 extern crate irq_safety;
 extern crate memory;
 extern crate pci; 
-extern crate owning_ref;
 extern crate nic_initialization;
 extern crate mlx_ethernet;
 extern crate kernel_config;
 extern crate memory_structs;
 extern crate nic_buffers;
 extern crate mpmc;
+extern crate hw_ring;
 #[macro_use] extern crate lazy_static;
 
 
 use spin::Once; 
-use alloc::{
-    vec::Vec,
-    boxed::Box
-};
+use alloc::vec::Vec;
 use irq_safety::MutexIrqSafe;
 use memory::{PhysicalAddress, MappedPages, create_contiguous_mapping};
-use pci::PciDevice;
-use owning_ref::BoxRefMut;
-use nic_initialization::{NIC_MAPPING_FLAGS, allocate_memory, init_rx_buf_pool};
+use pci::{PciDevice, PciEnableGuard, BarRegisters, PCI_COMMAND_MEMORY_SPACE, PCI_COMMAND_BUS_MASTER};
+use hw_ring::HwRing;
+use nic_initialization::{NIC_MAPPING_FLAGS, init_rx_buf_pool};
 use mlx_ethernet::{
     command_queue::{AccessRegisterOpMod, CommandBuilder, CommandOpcode, CommandQueue, CommandQueueEntry, HCACapabilities, ManagePagesOpMod, QueryHcaCapCurrentOpMod, QueryHcaCapMaxOpMod, QueryPagesOpMod}, 
     completion_queue::{CompletionQueue, CompletionQueueEntry, CompletionQueueDoorbellRecord}, 
@@ -44,6 +41,7 @@ use mlx_ethernet::{
     initialization_segment::InitializationSegment, 
     receive_queue::ReceiveQueue, 
     send_queue::SendQueue,
+    uar::UserAccessRegion,
     work_queue::{WorkQueueEntrySend, WorkQueueEntryReceive, DoorbellRecord}
 };
 use kernel_config::memory::PAGE_SIZE;
@@ -88,10 +86,10 @@ pub fn get_mlx5_nic() -> Option<&'static MutexIrqSafe<ConnectX5Nic>> {
 /// Struct representing a ConnectX-5 network interface card.
 #[allow(dead_code)]
 pub struct ConnectX5Nic {
-    /// Initialization segment base address
-    mem_base: PhysicalAddress,
+    /// See [`PciEnableGuard`] for why this is the first field.
+    pci_enable_guard: PciEnableGuard,
     /// Initialization segment
-    init_segment: BoxRefMut<MappedPages, InitializationSegment>,
+    init_segment: BarRegisters<InitializationSegment>,
     /// Command Queue
     command_queue: CommandQueue,
     /// Boot pages passed to the NIC. Once transferred, they should not be accessed by the driver.
@@ -146,20 +144,13 @@ impl ConnectX5Nic {
             return Err("The number of descriptors must be a power of two.");
         } 
 
-        // set the bus mastering bit for this PciDevice, which allows it to use DMA
-        mlx5_pci_dev.pci_set_command_bus_master_bit();
-
-        // retrieve the memory-mapped base address of the initialization segment
-        let mem_base = mlx5_pci_dev.determine_mem_base(0)?;
-        trace!("mlx5 mem base = {}", mem_base);
+        // set the memory space and bus mastering bits for this PciDevice, which allows it to use MMIO and DMA
+        let pci_enable_guard = mlx5_pci_dev.pci_enable(PCI_COMMAND_MEMORY_SPACE | PCI_COMMAND_BUS_MASTER, "mlx5");
 
-        let mem_size = mlx5_pci_dev.determine_mem_size(0);
-        trace!("mlx5 mem size = {}", mem_size);
+        // the initialization segment is at the beginning of BAR0
+        let mut init_segment = mlx5_pci_dev.pci_bar_registers::<InitializationSegment>(0, 0)?;
 
-        // map pages to the physical address given by mem_base as that is the intialization segment
-        let mut init_segment = ConnectX5Nic::map_init_segment(mem_base)?;
-
-        trace!("{:?}", init_segment);
+        trace!("{:?}", *init_segment);
         
         // find number of entries in command queue and stride
         let num_cmdq_entries = init_segment.num_cmdq_entries() as usize;
@@ -175,19 +166,12 @@ impl ConnectX5Nic {
             return Err("Command Queue layout is no longer accurate due to invalid assumption.");
         }
 
-        // calculate size of command queue
-        let size_in_bytes_of_cmdq = num_cmdq_entries * cmdq_stride;
-        trace!("total size in bytes of cmdq = {}", size_in_bytes_of_cmdq);
-    
-        // allocate mapped pages for the command queue
-        let (cmdq_mapped_pages, cmdq_starting_phys_addr) = create_contiguous_mapping(size_in_bytes_of_cmdq, NIC_MAPPING_FLAGS)?;
+        // allocate the command queue entries
+        let cmdq_entries = HwRing::<CommandQueueEntry>::new(num_cmdq_entries, NIC_MAPPING_FLAGS)?;
+        let cmdq_starting_phys_addr = cmdq_entries.phys_addr();
+        trace!("total size in bytes of cmdq = {}", cmdq_entries.size_in_bytes());
         trace!("cmdq mem base = {}", cmdq_starting_phys_addr);
-    
-        // cast our physically-contiguous MappedPages into a slice of command queue entries
-        let mut cmdq = CommandQueue::create(
-            BoxRefMut::new(Box::new(cmdq_mapped_pages)).try_map_mut(|mp| mp.as_slice_mut::<CommandQueueEntry>(0, num_cmdq_entries))?,
-            num_cmdq_entries
-        )?;
+        let mut cmdq = CommandQueue::create(cmdq_entries)?;
 
         // write physical location of command queue to initialization segment
         init_segment.set_physical_address_of_cmdq(cmdq_starting_phys_addr)?;
@@ -483,11 +467,10 @@ impl ConnectX5Nic {
         let (db_page, db_pa) = create_contiguous_mapping(core::mem::size_of::<DoorbellRecord>(), NIC_MAPPING_FLAGS)?;
         debug!("doorbell: {:#x}", db_pa);
 
-        // Allocate page for UAR. 
-        // For the given uar number i, the page is the ith page from the memory base retrieved from the PCI BAR
-        let uar_mem_base = mem_base + ((uar as usize) * PAGE_SIZE);
-        let uar_page = allocate_memory(uar_mem_base, PAGE_SIZE)?;
-        debug!("mmio: {:?}, uar: {:?}", mem_base, uar_mem_base);
+        // Map the UAR page. 
+        // For the given uar number i, the page is the ith page of BAR0
+        let uar_page = mlx5_pci_dev.pci_bar_registers::<UserAccessRegion>(0, (uar as usize) * PAGE_SIZE)?;
+        debug!("uar: {}, BAR0 offset: {:#X}", uar, uar_page.offset());
 
         // Create the SQ
         let completed_cmd = cmdq.create_and_execute_command(
@@ -626,7 +609,7 @@ impl ConnectX5Nic {
 
 
         let mlx5_nic = ConnectX5Nic {
-            mem_base,
+            pci_enable_guard,
             init_segment,
             command_queue: cmdq, 
             boot_pages: boot_mp,
@@ -645,12 +628,6 @@ impl ConnectX5Nic {
         Ok(nic_ref)
     }
     
-    /// Returns the memory-mapped initialization segment of the NIC
-    fn map_init_segment(mem_base: PhysicalAddress) -> Result<BoxRefMut<MappedPages, InitializationSegment>, &'static str> {
-        let mp = allocate_memory(mem_base, core::mem::size_of::<InitializationSegment>())?;
-        BoxRefMut::new(Box::new(mp)).try_map_mut(|mp| mp.as_type_mut::<InitializationSegment>(0))
-    }
-
     /// Allocates `num_pages` [`MappedPages`] each of the standard kernel page size [`PAGE_SIZE`].
     /// Returns a vector of the [`MappedPages`] and a vector of the [`PhysicalAddress`] of the pages.
     fn allocate_pages_for_nic(num_pages: usize) -> Result<(Vec<MappedPages>, Vec<PhysicalAddress>), &'static str> {
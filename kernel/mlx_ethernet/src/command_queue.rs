@@ -10,7 +10,7 @@ use volatile::{ReadOnly,Volatile};
 use bit_field::BitField;
 use zerocopy::{U32, FromBytes};
 use byteorder::BigEndian;
-use owning_ref:: BoxRefMut;
+use hw_ring::HwRing;
 use nic_initialization::NIC_MAPPING_FLAGS;
 use kernel_config::memory::PAGE_SIZE;
 use core::fmt;
@@ -509,12 +509,12 @@ impl Command<{CmdState::Initialized}> {
     /// and returns an initialized command.
     fn new(
         entry_num: usize, 
-        mut entry: CommandQueueEntry, 
+        entry: CommandQueueEntry, 
         input_mailbox_buffers: Box<[MailboxBuffer]>,
         output_mailbox_buffers: Box<[MailboxBuffer]>,
-        command_queue: &mut BoxRefMut<MappedPages, [CommandQueueEntry]>
+        command_queue: &mut HwRing<CommandQueueEntry>
     ) -> Command<{CmdState::Initialized}> {
-        core::mem::swap(&mut entry, &mut command_queue[entry_num]);
+        command_queue.write_volatile(entry_num, entry);
         Command {
             entry_num,
             input_mailbox_buffers,
@@ -694,7 +694,7 @@ impl CommandBuilder {
 /// (Section 8.24.1: HCA Command Queue)
 pub struct CommandQueue {
     /// Physically-contiguous command queue entries
-    entries: BoxRefMut<MappedPages, [CommandQueueEntry]>,
+    entries: HwRing<CommandQueueEntry>,
     /// Per-entry boolean flags to keep track of which entries are in use
     available_entries: Box<[bool]>,
     /// A random number that needs to be different for every command, and the same for all mailboxes that are part of a command.
@@ -708,10 +708,10 @@ impl CommandQueue {
     /// Create a command queue object.
     ///
     /// # Arguments
-    /// * `entries`: physically contiguous memory that is mapped as a slice of command queue entries.
-    /// * `num_cmdq_entries`: number of entries in the queue.
-    pub fn create(entries: BoxRefMut<MappedPages, [CommandQueueEntry]>, num_cmdq_entries: usize) -> Result<CommandQueue, &'static str> {
-        
+    /// * `entries`: the ring of command queue entries, whose length is the number of entries in the queue.
+    pub fn create(entries: HwRing<CommandQueueEntry>) -> Result<CommandQueue, &'static str> {
+        let num_cmdq_entries = entries.len();
+
         // initially, all command entries are available
         let available_entries = vec![true; num_cmdq_entries];
 
@@ -735,9 +735,9 @@ impl CommandQueue {
 
     /// Find an command queue entry that is not in use
     pub fn create_and_execute_command(&mut self, parameters: CommandBuilder, init_segment: &mut InitializationSegment) -> Result<Command<{CmdState::Completed}>, CommandQueueError> {
-        Ok( self.create_command(parameters)?
-                .post(init_segment)
-                .complete(&self) )
+        let command = self.create_command(parameters)?;
+        let posted = self.entries.publish(command.entry_num, |_| command.post(init_segment));
+        Ok(posted.complete(&self))
     }
     
     /// Fill in the fields of a command queue entry.
@@ -1212,7 +1212,7 @@ impl CommandQueue {
 
     /// Waits for ownership bit to be cleared, and then returns the command delivery status and the command return status.
     pub fn wait_for_command_completion(&self, command: &Command<{CmdState::Posted}>) {
-        while self.entries[command.entry_num].owned_by_hw() {}
+        while !self.entries.is_done(command.entry_num, |entry| !entry.owned_by_hw()) {}
     }
 
     pub fn get_command_status(&mut self, command: Command<{CmdState::Completed}>) -> Result<CommandCompletionStatus, CommandQueueError> {
@@ -28,6 +28,8 @@ extern crate libm;
 extern crate num_enum;
 extern crate nic_buffers;
 extern crate mpmc;
+extern crate hw_ring;
+extern crate pci;
 
 use kernel_config::memory::PAGE_SIZE;
 
@@ -38,7 +40,7 @@ pub mod completion_queue;
 pub mod send_queue;
 pub mod receive_queue;
 pub mod work_queue;
-mod uar;
+pub mod uar;
 mod flow_table;
 
 const UAR_MASK:                 u32 = 0xFF_FFFF;
@@ -11,6 +11,7 @@ use byteorder::BigEndian;
 use alloc::boxed::Box;
 use memory::{PhysicalAddress, MappedPages};
 use owning_ref:: BoxRefMut;
+use pci::BarRegisters;
 use core::fmt;
 use num_enum::TryFromPrimitive;
 use core::convert::TryFrom;
@@ -181,7 +182,7 @@ pub struct SendQueue {
     /// the doorbell for the SQ
     doorbell: BoxRefMut<MappedPages, DoorbellRecord>,
     /// the UAR page associated with the SQ
-    uar: BoxRefMut<MappedPages, UserAccessRegion>,
+    uar: BarRegisters<UserAccessRegion>,
     /// The number of WQEs that have been completed.
     /// From this we also calculate the next descriptor to use
     wqe_counter: u16,
@@ -205,7 +206,7 @@ impl SendQueue {
     /// * `num_entries`: number of entries in the SQ
     /// * `doorbell_mp`: memory that is to be transformed into a doorbell record. 
     /// The starting physical address should have been passed to the HCA when creating the SQ.   
-    /// * `uar_mp`: The UAR page that is associate with this SQ. 
+    /// * `uar`: The UAR page that is associated with this SQ, within the NIC's BAR0.
     /// * `sqn`: SQ number returned by the HCA
     /// * `tisn`: number of the TIS context associated with this SQ
     /// * `lkey`: the lkey used by the SQ
@@ -213,7 +214,7 @@ impl SendQueue {
         entries_mp: MappedPages, 
         num_entries: usize, 
         doorbell_mp: MappedPages, 
-        uar_mp: MappedPages, 
+        mut uar: BarRegisters<UserAccessRegion>, 
         sqn: Sqn,
         _tisn: Tisn,
         lkey: Lkey
@@ -226,8 +227,7 @@ impl SendQueue {
         // map the doorbell and initialize
         let mut doorbell = BoxRefMut::new(Box::new(doorbell_mp)).try_map_mut(|mp| mp.as_type_mut::<DoorbellRecord>(0))?;
         *doorbell = DoorbellRecord::default();
-        // map the uar and initialize
-        let mut uar = BoxRefMut::new(Box::new(uar_mp)).try_map_mut(|mp| mp.as_type_mut::<UserAccessRegion>(0))?;
+        // initialize the uar
         *uar = UserAccessRegion::default();
 
         Ok( SendQueue{entries, doorbell, uar, wqe_counter: 0, sqn, _tisn, lkey, uar_db: CurrentUARDoorbell::Even} )
@@ -15,7 +15,7 @@ use crate::send_queue::CurrentUARDoorbell;
 /// (PRM Section 8.2.2: UAR Page Format)
 #[derive(FromBytes)]
 #[repr(C)]
-pub(crate) struct UserAccessRegion {
+pub struct UserAccessRegion {
     _padding0:  [u8; 32],
     /// consumer index of the CQ
     cq_ci: Volatile<U32<BigEndian>>,
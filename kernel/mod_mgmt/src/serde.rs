@@ -32,7 +32,10 @@ pub struct SerializedCrate {
     /// The name of the crate.
     pub crate_name: String,
     /// A map containing all the sections of the crate.
-    pub sections: HashMap<Shndx, SerializedSection>,
+    ///
+    /// This is ordered by section index such that serializing the same crate
+    /// always produces identical output, which reproducible builds depend on.
+    pub sections: BTreeMap<Shndx, SerializedSection>,
     /// A set containing the global sectinos of the crate.
     pub global_sections: BTreeSet<Shndx>,
     /// A set containing the thread-local storage (TLS) sections of the crate.
@@ -24,4 +24,116 @@ pub trait NetworkInterfaceCard {
     /// If spoofed, it will return the spoofed MAC address, 
     /// otherwise it will return the regular MAC address defined by the NIC hardware.
     fn mac_address(&self) -> [u8; 6];
+
+    /// Returns the maximum transmission unit (MTU) that this NIC is configured with,
+    /// i.e., the largest payload of an Ethernet frame that it can send and receive.
+    /// 
+    /// By default, this is the standard Ethernet MTU of 1500 bytes.
+    fn mtu(&self) -> u16 {
+        1500
+    }
+}
+
+
+/// The speed of an Ethernet link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkSpeed {
+    Mbps10,
+    Mbps100,
+    Mbps1000,
+    Mbps10000,
+}
+
+/// The duplex mode of an Ethernet link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duplex {
+    Half,
+    Full,
+}
+
+/// The current state of a NIC's link, as reported by its MAC/PHY.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkState {
+    /// Whether the link is currently up.
+    pub up: bool,
+    /// The negotiated (or forced) speed, if known.
+    pub speed: Option<LinkSpeed>,
+    /// The negotiated (or forced) duplex mode, if known.
+    pub duplex: Option<Duplex>,
+}
+
+/// The set of abilities that a link partner advertised during auto-negotiation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkAbilities {
+    pub half_duplex_10: bool,
+    pub full_duplex_10: bool,
+    pub half_duplex_100: bool,
+    pub full_duplex_100: bool,
+    pub half_duplex_1000: bool,
+    pub full_duplex_1000: bool,
+    pub full_duplex_10000: bool,
+    /// Whether the link partner supports symmetric PAUSE flow control.
+    pub pause: bool,
+}
+
+/// A trait for NIC drivers that are able to manage their link,
+/// typically by accessing the PHY's MII registers or the MAC's auto-negotiation registers.
+pub trait LinkControl {
+    /// Returns the current state of the link.
+    fn link_state(&self) -> LinkState;
+
+    /// Disables auto-negotiation and forces the link to the given `speed` and `duplex` mode.
+    /// 
+    /// Returns an error if the hardware cannot operate at the requested speed/duplex
+    /// without auto-negotiation.
+    fn force_link(&mut self, speed: LinkSpeed, duplex: Duplex) -> Result<(), &'static str>;
+
+    /// Re-enables auto-negotiation (if it was disabled) and restarts it.
+    fn restart_autonegotiation(&mut self) -> Result<(), &'static str>;
+
+    /// Returns the abilities that the link partner advertised during the last auto-negotiation.
+    /// 
+    /// Returns an error if auto-negotiation has not completed
+    /// or if the hardware doesn't expose the link partner's abilities.
+    fn link_partner_abilities(&mut self) -> Result<LinkAbilities, &'static str>;
+}
+
+
+/// A trait for NIC drivers that can program their hardware receive filters,
+/// such that the network stack can receive the unicast and multicast traffic it needs
+/// without putting the NIC into promiscuous mode.
+/// 
+/// The network stack should re-program these filters whenever its addresses change,
+/// e.g., after a DHCP lease is obtained or an IPv6 address is added.
+pub trait ReceiveFilter {
+    /// Sets the unicast MAC address that this NIC accepts frames for.
+    fn set_unicast_address(&mut self, mac_address: [u8; 6]) -> Result<(), &'static str>;
+
+    /// Replaces the set of multicast MAC addresses that this NIC accepts frames for.
+    /// 
+    /// Hardware that filters multicast frames with an inexact hash table
+    /// may still accept some frames destined to other multicast addresses.
+    fn set_multicast_addresses(&mut self, multicast_addresses: &[[u8; 6]]) -> Result<(), &'static str>;
+
+    /// Enables or disables unicast and multicast promiscuous mode,
+    /// in which the NIC accepts all frames regardless of the above filters.
+    fn set_promiscuous(&mut self, enable: bool) -> Result<(), &'static str>;
+}
+
+/// Returns the multicast MAC address that frames sent to the given IPv4 multicast `group` are addressed to,
+/// as defined in RFC 1112.
+pub fn ipv4_multicast_mac_address(group: [u8; 4]) -> [u8; 6] {
+    [0x01, 0x00, 0x5E, group[1] & 0x7F, group[2], group[3]]
+}
+
+/// Returns the multicast MAC address that frames sent to the given IPv6 multicast `group` are addressed to,
+/// as defined in RFC 2464.
+pub fn ipv6_multicast_mac_address(group: [u8; 16]) -> [u8; 6] {
+    [0x33, 0x33, group[12], group[13], group[14], group[15]]
+}
+
+/// Returns the multicast MAC address of the solicited-node multicast group for the given IPv6 `address`,
+/// which must be received for IPv6 neighbor discovery to work.
+pub fn ipv6_solicited_node_mac_address(address: [u8; 16]) -> [u8; 6] {
+    [0x33, 0x33, 0xFF, address[13], address[14], address[15]]
 }
@@ -106,8 +106,15 @@ impl Drop for ReceiveBuffer {
         // we set the length to 0 as a quick way to "clear" the buffer. We could also zero out the whole MP. 
 
         // Now, we can add the new receive buffer to the pool 
-        if let Err(_e) = self.pool.push(new_rb) {
-            error!("NIC: couldn't return dropped ReceiveBuffer to pool, buf length: {}, phys_addr: {:#X}", _e.length, _e.phys_addr);
+        if let Err(mut rejected) = self.pool.push(new_rb) {
+            error!("NIC: couldn't return dropped ReceiveBuffer to pool, buf length: {}, phys_addr: {:#X}", rejected.length, rejected.phys_addr);
+            // Dropping the rejected buffer normally would re-enter this function and try to push it again,
+            // so we take its memory out and free that directly, then forget the now-empty buffer.
+            // Forgetting it cannot leak anything else: its other fields are a plain address, a length,
+            // and a `&'static` reference to the pool, none of which own any resources.
+            let mp = core::mem::replace(&mut rejected.mp, MappedPages::empty());
+            core::mem::forget(rejected);
+            drop(mp);
         }
 
         // `self` will be automatically dropped now, which only has the empty MP object.
@@ -116,4 +123,14 @@ impl Drop for ReceiveBuffer {
 
 
 /// A network (e.g., Ethernet) frame that has been received by the NIC.
-pub struct ReceivedFrame(pub Vec<ReceiveBuffer>);
+/// 
+/// The second field is the hardware receive timestamp of this frame in nanoseconds,
+/// which is only present if the NIC supports timestamping and it was enabled.
+pub struct ReceivedFrame(pub Vec<ReceiveBuffer>, pub Option<u64>);
+
+impl ReceivedFrame {
+    /// Returns the hardware timestamp (in nanoseconds) at which this frame was received, if any.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.1
+    }
+}
@@ -9,7 +9,7 @@ extern crate alloc;
 extern crate memory;
 extern crate mpmc;
 extern crate pci;
-extern crate owning_ref;
+extern crate hw_ring;
 extern crate intel_ethernet;
 extern crate nic_buffers;
 extern crate volatile;
@@ -17,14 +17,11 @@ extern crate nic_queues;
 
 use memory::{EntryFlags, PhysicalAddress, allocate_pages_by_bytes, allocate_frames_by_bytes_at, get_kernel_mmi_ref, MappedPages, create_contiguous_mapping};
 use pci::{PciDevice};
-use alloc::{
-    vec::Vec,
-    boxed::Box,
-};
-use owning_ref::BoxRefMut;
+use alloc::vec::Vec;
+use hw_ring::HwRing;
 use intel_ethernet::descriptors::{RxDescriptor, TxDescriptor};
 use nic_buffers::ReceiveBuffer;
-use nic_queues::{RxQueueRegisters, TxQueueRegisters};
+use nic_queues::{RxQueueRegisters, TxQueueRegisters, RssRegisters};
 
 /// The mapping flags used for pages that the NIC will map.
 pub const NIC_MAPPING_FLAGS: EntryFlags = EntryFlags::from_bits_truncate(
@@ -34,6 +31,28 @@ pub const NIC_MAPPING_FLAGS: EntryFlags = EntryFlags::from_bits_truncate(
     EntryFlags::NO_EXECUTE.bits()
 );
 
+/// The standard Ethernet MTU, i.e., the largest payload of a frame when jumbo frames are not used.
+pub const STANDARD_MTU: u16 = 1500;
+
+/// The largest MTU supported for jumbo frames.
+pub const MAX_JUMBO_MTU: u16 = 9000;
+
+/// The number of bytes in an Ethernet frame in addition to its payload:
+/// the 14-byte header, one 4-byte VLAN tag, and the 4-byte frame check sequence.
+const ETHERNET_FRAME_OVERHEAD: u16 = 14 + 4 + 4;
+
+/// Returns the size in bytes of the largest Ethernet frame that can carry a payload of `mtu` bytes,
+/// which is the size that a NIC must be configured to accept in order to not truncate or drop such frames.
+///
+/// Returns an error if `mtu` is zero or larger than [`MAX_JUMBO_MTU`].
+pub fn max_frame_size(mtu: u16) -> Result<u16, &'static str> {
+    if mtu == 0 || mtu > MAX_JUMBO_MTU {
+        error!("max_frame_size(): MTU {} is not between 1 and {}", mtu, MAX_JUMBO_MTU);
+        return Err("max_frame_size(): unsupported MTU");
+    }
+    Ok(mtu + ETHERNET_FRAME_OVERHEAD)
+}
+
 
 /// Allocates memory for the NIC registers
 /// 
@@ -70,11 +89,18 @@ pub fn allocate_memory(mem_base: PhysicalAddress, mem_size_in_bytes: usize) -> R
 
 /// Initialize the receive buffer pool from where receive buffers are taken and returned
 /// 
+/// Each buffer is physically contiguous, so a buffer can be large enough to hold an entire jumbo frame
+/// (see [`max_frame_size()`]). Alternatively, smaller buffers can be used if the NIC is configured
+/// to split a large frame across multiple receive descriptors.
+/// 
 /// # Arguments
 /// * `num_rx_buffers`: number of buffers that are initially added to the pool 
 /// * `buffer_size`: size of the receive buffers in bytes
 /// * `rx_buffer_pool`: buffer pool to initialize
 pub fn init_rx_buf_pool(num_rx_buffers: usize, buffer_size: u16, rx_buffer_pool: &'static mpmc::Queue<ReceiveBuffer>) -> Result<(), &'static str> {
+    if buffer_size == 0 {
+        return Err("init_rx_buf_pool(): receive buffers must not be empty");
+    }
     let length = buffer_size;
     for _i in 0..num_rx_buffers {
         let (mp, phys_addr) = create_contiguous_mapping(length as usize, NIC_MAPPING_FLAGS)?; 
@@ -97,15 +123,12 @@ pub fn init_rx_buf_pool(num_rx_buffers: usize, buffer_size: u16, rx_buffer_pool:
 /// * `buffer_size`: size of each buffer in the pool in bytes
 /// * `rxq_regs`: registers needed to set up a receive queue 
 pub fn init_rx_queue<T: RxDescriptor, S:RxQueueRegisters>(num_desc: usize, rx_buffer_pool: &'static mpmc::Queue<ReceiveBuffer>, buffer_size: usize, rxq_regs: &mut S)
-    -> Result<(BoxRefMut<MappedPages, [T]>, Vec<ReceiveBuffer>), &'static str> 
+    -> Result<(HwRing<T>, Vec<ReceiveBuffer>), &'static str> 
 {    
-    let size_in_bytes_of_all_rx_descs_per_queue = num_desc * core::mem::size_of::<T>();
-    
-    // Rx descriptors must be 128 byte-aligned, which is satisfied below because it's aligned to a page boundary.
-    let (rx_descs_mapped_pages, rx_descs_starting_phys_addr) = create_contiguous_mapping(size_in_bytes_of_all_rx_descs_per_queue, NIC_MAPPING_FLAGS)?;
-
-    // cast our physically-contiguous MappedPages into a slice of receive descriptors
-    let mut rx_descs = BoxRefMut::new(Box::new(rx_descs_mapped_pages)).try_map_mut(|mp| mp.as_slice_mut::<T>(0, num_desc))?;
+    // Rx descriptors must be 128 byte-aligned, which is satisfied because the ring is aligned to a page boundary.
+    let mut rx_descs = HwRing::<T>::new(num_desc, NIC_MAPPING_FLAGS)?;
+    let size_in_bytes_of_all_rx_descs_per_queue = rx_descs.size_in_bytes();
+    let rx_descs_starting_phys_addr = rx_descs.phys_addr();
 
     // now that we've created the rx descriptors, we can fill them in with initial values
     let mut rx_bufs_in_use: Vec<ReceiveBuffer> = Vec::with_capacity(num_desc);
@@ -151,16 +174,12 @@ pub fn init_rx_queue<T: RxDescriptor, S:RxQueueRegisters>(num_desc: usize, rx_bu
 /// * `num_desc`: number of descriptors in the queue
 /// * `txq_regs`: registers needed to set up a transmit queue
 pub fn init_tx_queue<T: TxDescriptor, S: TxQueueRegisters>(num_desc: usize, txq_regs: &mut S) 
-    -> Result<BoxRefMut<MappedPages, [T]>, &'static str> 
+    -> Result<HwRing<T>, &'static str> 
 {
-    let size_in_bytes_of_all_tx_descs = num_desc * core::mem::size_of::<T>();
-    
-    // Tx descriptors must be 128 byte-aligned, which is satisfied below because it's aligned to a page boundary.
-    let (tx_descs_mapped_pages, tx_descs_starting_phys_addr) = create_contiguous_mapping(size_in_bytes_of_all_tx_descs, NIC_MAPPING_FLAGS)?;
-
-    // cast our physically-contiguous MappedPages into a slice of transmit descriptors
-    let mut tx_descs = BoxRefMut::new(Box::new(tx_descs_mapped_pages))
-        .try_map_mut(|mp| mp.as_slice_mut::<T>(0, num_desc))?;
+    // Tx descriptors must be 128 byte-aligned, which is satisfied because the ring is aligned to a page boundary.
+    let mut tx_descs = HwRing::<T>::new(num_desc, NIC_MAPPING_FLAGS)?;
+    let size_in_bytes_of_all_tx_descs = tx_descs.size_in_bytes();
+    let tx_descs_starting_phys_addr = tx_descs.phys_addr();
 
     // now that we've created the tx descriptors, we can fill them in with initial values
     for td in tx_descs.iter_mut() {
@@ -185,3 +204,62 @@ pub fn init_tx_queue<T: TxDescriptor, S: TxQueueRegisters>(num_desc: usize, txq_
     Ok(tx_descs)
 }
 
+/// Creates and initializes a receive descriptor queue for each of the given sets of queue registers.
+///
+/// Returns the descriptor ring and receive buffers of each queue, in the same order as `rxq_regs`.
+///
+/// # Arguments
+/// * `num_desc`: number of descriptors in each queue
+/// * `rx_buffer_pool`: pool from which to take receive buffers
+/// * `buffer_size`: size of each buffer in the pool in bytes
+/// * `rxq_regs`: registers needed to set up each receive queue
+pub fn init_rx_queues<T: RxDescriptor, S: RxQueueRegisters>(num_desc: usize, rx_buffer_pool: &'static mpmc::Queue<ReceiveBuffer>, buffer_size: usize, rxq_regs: &mut [S])
+    -> Result<Vec<(HwRing<T>, Vec<ReceiveBuffer>)>, &'static str>
+{
+    rxq_regs.iter_mut()
+        .map(|regs| init_rx_queue(num_desc, rx_buffer_pool, buffer_size, regs))
+        .collect()
+}
+
+/// Creates and initializes a transmit descriptor queue for each of the given sets of queue registers.
+///
+/// Returns the descriptor ring of each queue, in the same order as `txq_regs`.
+///
+/// # Arguments
+/// * `num_desc`: number of descriptors in each queue
+/// * `txq_regs`: registers needed to set up each transmit queue
+pub fn init_tx_queues<T: TxDescriptor, S: TxQueueRegisters>(num_desc: usize, txq_regs: &mut [S])
+    -> Result<Vec<HwRing<T>>, &'static str>
+{
+    txq_regs.iter_mut()
+        .map(|regs| init_tx_queue(num_desc, regs))
+        .collect()
+}
+
+/// Enables Receive Side Scaling (RSS) to distribute received packets across the first `num_queues` receive queues.
+///
+/// The redirection table is filled in round-robin order, such that each queue receives 
+/// an equal share of the hash values. The receive queues themselves must be initialized separately,
+/// e.g., with [`init_rx_queues()`].
+///
+/// # Arguments
+/// * `rss_regs`: registers needed to configure RSS
+/// * `num_queues`: number of receive queues that packets are distributed across
+/// * `hash_key`: key used by the hash function, which must be exactly as long as the NIC's hash key;
+///    it should be random to prevent an attacker from directing all packets to one queue.
+pub fn init_rss<R: RssRegisters>(rss_regs: &mut R, num_queues: u8, hash_key: &[u8]) -> Result<(), &'static str> {
+    if num_queues == 0 {
+        return Err("init_rss(): the number of receive queues must be non-zero");
+    }
+    if hash_key.len() != rss_regs.hash_key_len() {
+        error!("init_rss(): hash key is {} bytes, but the NIC requires {} bytes", hash_key.len(), rss_regs.hash_key_len());
+        return Err("init_rss(): hash key has the wrong length");
+    }
+
+    rss_regs.set_hash_key(hash_key);
+    for index in 0..rss_regs.redirection_table_len() {
+        rss_regs.set_redirection_table_entry(index, (index % num_queues as usize) as u8);
+    }
+    rss_regs.enable_rss();
+    Ok(())
+}
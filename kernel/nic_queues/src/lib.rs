@@ -13,16 +13,18 @@ extern crate alloc;
 extern crate memory;
 extern crate intel_ethernet;
 extern crate nic_buffers;
-extern crate owning_ref;
+extern crate hw_ring;
+extern crate packet_capture;
 
-use owning_ref::BoxRefMut;
 use alloc::{
     vec::Vec,
     collections::VecDeque
 };
-use memory::{MappedPages, create_contiguous_mapping, EntryFlags};
+use memory::{create_contiguous_mapping, EntryFlags};
+use hw_ring::HwRing;
 use intel_ethernet::descriptors::{RxDescriptor, TxDescriptor};
 use nic_buffers::{ReceiveBuffer, ReceivedFrame, TransmitBuffer};
+use packet_capture::Direction;
 
 /// The mapping flags used for pages that the NIC will map.
 pub const NIC_MAPPING_FLAGS: EntryFlags = EntryFlags::from_bits_truncate(
@@ -52,6 +54,22 @@ pub trait TxQueueRegisters {
     fn set_tdt(&mut self, value: u32);
 }
 
+/// The register trait that gives access to the registers used to configure Receive Side Scaling (RSS),
+/// which distributes received packets across multiple receive queues based on a hash of their headers.
+/// The RSS registers can only be accessed by the physical NIC.
+pub trait RssRegisters {
+    /// Returns the number of entries in the redirection table, which maps hash values to receive queues.
+    fn redirection_table_len(&self) -> usize;
+    /// Directs packets whose hash selects the redirection table entry at `index` to the receive queue `queue`.
+    fn set_redirection_table_entry(&mut self, index: usize, queue: u8);
+    /// Returns the length in bytes of the key used by the hash function.
+    fn hash_key_len(&self) -> usize;
+    /// Sets the key used by the hash function, which must be exactly `hash_key_len()` bytes.
+    fn set_hash_key(&mut self, key: &[u8]);
+    /// Enables RSS, using the NIC's default set of header fields as input to the hash function.
+    fn enable_rss(&mut self);
+}
+
 /// A struct that holds all information for one receive queue.
 /// There should be one such object per queue.
 pub struct RxQueue<S: RxQueueRegisters, T: RxDescriptor> {
@@ -59,10 +77,8 @@ pub struct RxQueue<S: RxQueueRegisters, T: RxDescriptor> {
     pub id: u8,
     /// Registers for this receive queue
     pub regs: S,
-    /// Receive descriptors
-    pub rx_descs: BoxRefMut<MappedPages, [T]>,
-    /// The number of receive descriptors in the descriptor ring
-    pub num_rx_descs: u16,
+    /// The ring of receive descriptors shared with the NIC
+    pub rx_descs: HwRing<T>,
     /// Current receive descriptor index
     pub rx_cur: u16,
     /// The list of rx buffers, in which the index in the vector corresponds to the index in `rx_descs`.
@@ -88,12 +104,27 @@ impl<S: RxQueueRegisters, T: RxDescriptor> RxQueue<S,T> {
     /// Polls the queue and removes all received packets from it.
     /// The received packets are stored in the receive queue's `received_frames` FIFO queue.
     pub fn poll_queue_and_store_received_packets(&mut self) -> Result<(), &'static str> {
+        self.poll_queue_and_store_received_packets_with_timestamps(|| None)
+    }
+
+    /// Same as [`poll_queue_and_store_received_packets()`](Self::poll_queue_and_store_received_packets),
+    /// but for every frame that the NIC marked as timestamped, `read_timestamp` is invoked
+    /// to retrieve its hardware receive timestamp, which is then stored alongside the frame.
+    pub fn poll_queue_and_store_received_packets_with_timestamps<F>(&mut self, mut read_timestamp: F) -> Result<(), &'static str> 
+        where F: FnMut() -> Option<u64>
+    {
         let mut cur = self.rx_cur as usize;
        
         let mut receive_buffers_in_frame: Vec<ReceiveBuffer> = Vec::new();
         let mut _total_packet_length: u16 = 0;
 
-        while self.rx_descs[cur].descriptor_done() {
+        while self.rx_descs.is_done(cur, |desc| desc.descriptor_done()) {
+            // Only start consuming a frame once the NIC has finished writing all of its descriptors,
+            // such that a frame spanning multiple receive buffers (e.g., a jumbo frame) isn't split across polls.
+            if receive_buffers_in_frame.is_empty() && !self.frame_complete(cur) {
+                break;
+            }
+
             // get information about the current receive buffer
             let length = self.rx_descs[cur].length();
             _total_packet_length += length as u16;
@@ -124,15 +155,24 @@ impl<S: RxQueueRegisters, T: RxDescriptor> RxQueue<S,T> {
             receive_buffers_in_frame.push(current_rx_buf);
 
             // move on to the next receive buffer to see if it's ready for us to take
-            self.rx_cur = (cur as u16 + 1) % self.num_rx_descs;
-            self.regs.set_rdt(cur as u32); 
+            self.rx_cur = self.rx_descs.next_index(cur) as u16;
+            let regs = &mut self.regs;
+            self.rx_descs.publish(cur, |tail| regs.set_rdt(tail as u32));
 
             if self.rx_descs[cur].end_of_packet() {
                 let buffers = core::mem::replace(&mut receive_buffers_in_frame, Vec::new());
-                self.received_frames.push_back(ReceivedFrame(buffers));
-            } else {
-                warn!("NIC::poll_queue_and_store_received_packets(): Received multi-rxbuffer frame, this scenario not fully tested!");
+                let timestamp = if self.rx_descs[cur].timestamped() { read_timestamp() } else { None };
+                if packet_capture::is_enabled() {
+                    packet_capture::capture(
+                        Direction::Receive,
+                        self.id,
+                        buffers.iter().filter_map(|b| b.as_slice::<u8>(0, b.length as usize).ok()),
+                    );
+                }
+                self.received_frames.push_back(ReceivedFrame(buffers, timestamp));
+                _total_packet_length = 0;
             }
+            // Otherwise, this frame spans multiple receive buffers, so continue on to the next descriptor.
             self.rx_descs[cur].reset_status();
             cur = self.rx_cur as usize;
         }
@@ -140,6 +180,25 @@ impl<S: RxQueueRegisters, T: RxDescriptor> RxQueue<S,T> {
         Ok(())
     }
 
+    /// Returns `true` if the NIC has finished writing every descriptor of the frame that begins at index `start`,
+    /// i.e., all descriptors up to and including the one marked as the end of the packet.
+    ///
+    /// If every descriptor in the ring is done but none marks the end of a packet,
+    /// this also returns `true`, since the frame cannot grow any further.
+    fn frame_complete(&self, start: usize) -> bool {
+        let mut index = start;
+        for _ in 0..self.rx_descs.len() {
+            if !self.rx_descs.is_done(index, |desc| desc.descriptor_done()) {
+                return false;
+            }
+            if self.rx_descs[index].end_of_packet() {
+                return true;
+            }
+            index = self.rx_descs.next_index(index);
+        }
+        true
+    }
+
     /// Returns the earliest received ethernet frame.
     pub fn return_frame(&mut self) -> Option<ReceivedFrame> {
         self.received_frames.pop_front()
@@ -153,32 +212,122 @@ pub struct TxQueue<S: TxQueueRegisters, T: TxDescriptor> {
     pub id: u8,
     /// Registers for this transmit queue
     pub regs: S,
-    /// Transmit descriptors 
-    pub tx_descs: BoxRefMut<MappedPages, [T]>,
-    /// The number of transmit descriptors in the descriptor ring
-    pub num_tx_descs: u16,
+    /// The ring of transmit descriptors shared with the NIC
+    pub tx_descs: HwRing<T>,
     /// Current transmit descriptor index
     pub tx_cur: u16,
     /// The cpu which this queue is mapped to. 
     /// This in itself doesn't guarantee anything but we use this value when setting the cpu id for interrupts and DCA.
-    pub cpu_id : Option<u8>
+    pub cpu_id : Option<u8>,
+    /// The buffers that have been handed to the NIC but not yet reclaimed, in the order they were sent.
+    /// A buffer must be kept alive until the NIC has finished reading it.
+    pub tx_bufs_in_flight: VecDeque<InFlightTransmitBuffer>,
+    /// The Report Status (RS) bit is set on one out of every `rs_threshold` descriptors
+    /// sent through [`send_batch()`](TxQueue::send_batch), as well as on the last descriptor of each batch.
+    /// A value of 1 requests a status write-back for every descriptor.
+    pub rs_threshold: u16,
+}
+
+/// A `TransmitBuffer` that is owned by a `TxQueue` until the NIC has finished sending it.
+pub struct InFlightTransmitBuffer {
+    /// The index of the descriptor that points to this buffer.
+    pub desc_index: u16,
+    /// Whether the RS bit was set in that descriptor, i.e., whether its completion will be written back.
+    pub report_status: bool,
+    pub buffer: TransmitBuffer,
 }
 
 impl<S: TxQueueRegisters, T: TxDescriptor> TxQueue<S,T> {
-    /// Sends a packet on the transmit queue
+    /// Sends a packet on the transmit queue and waits for it to be sent.
     /// 
     /// # Arguments:
     /// * `transmit_buffer`: buffer containing the packet to be sent
     pub fn send_on_queue(&mut self, transmit_buffer: TransmitBuffer) {
-        self.tx_descs[self.tx_cur as usize].send(transmit_buffer.phys_addr, transmit_buffer.length);  
-        // update the tx_cur value to hold the next free descriptor
-        let old_cur = self.tx_cur;
-        self.tx_cur = (self.tx_cur + 1) % self.num_tx_descs;
-        // update the tdt register by 1 so that it knows the previous descriptor has been used
-        // and has a packet to be sent
-        self.regs.set_tdt(self.tx_cur as u32);
-        // Wait for the packet to be sent
-        self.tx_descs[old_cur as usize].wait_for_packet_tx();
+        let desc_index = self.tx_cur;
+        self.send_batch(core::iter::once(transmit_buffer));
+        // Wait for the packet to be sent, which also means all previous packets have been sent
+        while !self.tx_descs.is_done(desc_index as usize, |desc| desc.descriptor_done()) { }
+        self.reap_completed_transmits();
     }
-}
 
+    /// Sends all the given packets on the transmit queue without waiting for them to be sent.
+    /// 
+    /// The tail register is only updated once for the whole batch (unless the ring fills up),
+    /// and a status write-back is only requested every `rs_threshold` descriptors.
+    /// Completed buffers are reclaimed as needed when the ring is full, 
+    /// or explicitly via [`reap_completed_transmits()`](TxQueue::reap_completed_transmits).
+    pub fn send_batch<I: IntoIterator<Item = TransmitBuffer>>(&mut self, transmit_buffers: I) {
+        // An RS descriptor must always be among the in-flight descriptors once the ring is full,
+        // otherwise there would be no way to find out that any of them have completed.
+        let rs_threshold = core::cmp::max(1, core::cmp::min(self.rs_threshold, self.tx_descs.len() as u16 / 2));
+        // Keep one descriptor unused, such that the tail never catches up to the head.
+        let max_in_flight = self.tx_descs.len() - 1;
+        let mut descs_since_rs = self.tx_bufs_in_flight.iter().rev()
+            .take_while(|b| !b.report_status)
+            .count() as u16;
+
+        let mut transmit_buffers = transmit_buffers.into_iter().peekable();
+        let mut published_cur = self.tx_cur;
+        while let Some(transmit_buffer) = transmit_buffers.next() {
+            while self.tx_bufs_in_flight.len() >= max_in_flight {
+                if published_cur != self.tx_cur {
+                    let regs = &mut self.regs;
+                    self.tx_descs.publish(self.tx_cur as usize, |tail| regs.set_tdt(tail as u32));
+                    published_cur = self.tx_cur;
+                }
+                if self.reap_completed_transmits() == 0 {
+                    self.wait_for_oldest_report();
+                }
+            }
+
+            descs_since_rs += 1;
+            let report_status = descs_since_rs >= rs_threshold || transmit_buffers.peek().is_none();
+            if report_status {
+                descs_since_rs = 0;
+            }
+
+            if packet_capture::is_enabled() {
+                if let Ok(bytes) = transmit_buffer.as_slice::<u8>(0, transmit_buffer.length as usize) {
+                    packet_capture::capture(Direction::Transmit, self.id, core::iter::once(bytes));
+                }
+            }
+
+            let desc_index = self.tx_cur;
+            self.tx_descs[desc_index as usize].send_batched(transmit_buffer.phys_addr, transmit_buffer.length, report_status);
+            self.tx_bufs_in_flight.push_back(InFlightTransmitBuffer { desc_index, report_status, buffer: transmit_buffer });
+            // update the tx_cur value to hold the next free descriptor
+            self.tx_cur = self.tx_descs.next_index(self.tx_cur as usize) as u16;
+        }
+
+        // update the tdt register so that the NIC knows the previous descriptors have packets to be sent
+        if published_cur != self.tx_cur {
+            let regs = &mut self.regs;
+            self.tx_descs.publish(self.tx_cur as usize, |tail| regs.set_tdt(tail as u32));
+        }
+    }
+
+    /// Reclaims the buffers of all packets that the NIC has reported as sent.
+    /// 
+    /// Returns the number of buffers that were reclaimed.
+    pub fn reap_completed_transmits(&mut self) -> usize {
+        let mut reaped = 0;
+        // Descriptors complete in order, so everything up to a done RS descriptor has been sent.
+        while let Some(pos) = self.tx_bufs_in_flight.iter().position(|b| b.report_status) {
+            let desc_index = self.tx_bufs_in_flight[pos].desc_index;
+            if !self.tx_descs.is_done(desc_index as usize, |desc| desc.descriptor_done()) {
+                break;
+            }
+            // dropping the `TransmitBuffer`s frees them
+            self.tx_bufs_in_flight.drain(..=pos);
+            reaped += pos + 1;
+        }
+        reaped
+    }
+
+    /// Waits until the oldest in-flight descriptor with the RS bit set has been sent.
+    fn wait_for_oldest_report(&self) {
+        if let Some(b) = self.tx_bufs_in_flight.iter().find(|b| b.report_status) {
+            while !self.tx_descs.is_done(b.desc_index as usize, |desc| desc.descriptor_done()) { }
+        }
+    }
+}
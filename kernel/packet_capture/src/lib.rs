@@ -0,0 +1,167 @@
+//! A packet capture tap for debugging network drivers, similar to what `tcpdump` relies on.
+//! 
+//! When a capture is active, NIC queues copy every frame they receive or transmit
+//! into a bounded capture ring by calling [`capture()`].
+//! The captured frames can then be drained and written out in the pcap file format,
+//! using [`pcap_file_header()`] and [`CapturedFrame::pcap_record_header()`].
+//! 
+//! Capturing is disabled by default, in which case the tap costs a single atomic load per frame.
+
+#![no_std]
+
+extern crate alloc;
+extern crate irq_safety;
+extern crate tsc;
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use alloc::{
+    vec::Vec,
+    collections::VecDeque,
+};
+use irq_safety::MutexIrqSafe;
+
+/// The pcap magic number indicating that record timestamps are in seconds and nanoseconds.
+const PCAP_MAGIC_NANOSECONDS: u32 = 0xA1B2_3C4D;
+const PCAP_VERSION_MAJOR: u16 = 2;
+const PCAP_VERSION_MINOR: u16 = 4;
+/// The pcap link-layer header type for Ethernet.
+const PCAP_LINKTYPE_ETHERNET: u32 = 1;
+
+/// The default maximum number of frames held in the capture ring.
+pub const DEFAULT_CAPACITY: usize = 1024;
+/// The default maximum number of bytes captured from each frame.
+pub const DEFAULT_SNAPLEN: usize = 1514;
+
+/// Whether a frame was received or transmitted by the NIC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Receive,
+    Transmit,
+}
+
+/// A frame copied into the capture ring.
+pub struct CapturedFrame {
+    /// The value of the TSC when this frame was captured.
+    pub tsc_ticks: u64,
+    /// Whether this frame was received or transmitted.
+    pub direction: Direction,
+    /// The ID of the NIC queue that this frame passed through.
+    pub queue_id: u8,
+    /// The full length of the frame, which may be larger than `data.len()` 
+    /// if the frame was truncated to the snapshot length.
+    pub original_length: usize,
+    /// The captured bytes of the frame.
+    pub data: Vec<u8>,
+}
+
+impl CapturedFrame {
+    /// Returns the 16-byte pcap record header for this frame, 
+    /// given its timestamp in nanoseconds since the beginning of the capture.
+    pub fn pcap_record_header(&self, timestamp_ns: u64) -> [u8; 16] {
+        let mut header = [0u8; 16];
+        header[0..4].copy_from_slice(&((timestamp_ns / 1_000_000_000) as u32).to_le_bytes());
+        header[4..8].copy_from_slice(&((timestamp_ns % 1_000_000_000) as u32).to_le_bytes());
+        header[8..12].copy_from_slice(&(self.data.len() as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&(self.original_length as u32).to_le_bytes());
+        header
+    }
+}
+
+/// Returns the 24-byte pcap file header that must precede all records in a pcap file.
+pub fn pcap_file_header(snaplen: usize) -> [u8; 24] {
+    let mut header = [0u8; 24];
+    header[0..4].copy_from_slice(&PCAP_MAGIC_NANOSECONDS.to_le_bytes());
+    header[4..6].copy_from_slice(&PCAP_VERSION_MAJOR.to_le_bytes());
+    header[6..8].copy_from_slice(&PCAP_VERSION_MINOR.to_le_bytes());
+    // bytes 8..16 are the timezone offset and timestamp accuracy, which are always 0
+    header[16..20].copy_from_slice(&(snaplen as u32).to_le_bytes());
+    header[20..24].copy_from_slice(&PCAP_LINKTYPE_ETHERNET.to_le_bytes());
+    header
+}
+
+
+/// The bounded ring that captured frames are stored in.
+struct CaptureRing {
+    frames: VecDeque<CapturedFrame>,
+    capacity: usize,
+    snaplen: usize,
+}
+
+/// Whether a capture is currently active. Checked before taking the lock on `CAPTURE_RING`.
+static CAPTURE_ENABLED: AtomicBool = AtomicBool::new(false);
+/// The number of frames dropped because the capture ring was full.
+static DROPPED_FRAMES: AtomicUsize = AtomicUsize::new(0);
+/// The capture ring, which is created when the first capture is started.
+static CAPTURE_RING: MutexIrqSafe<Option<CaptureRing>> = MutexIrqSafe::new(None);
+
+/// Starts capturing frames, discarding any previously-captured frames.
+/// 
+/// # Arguments
+/// * `capacity`: the maximum number of frames held in the ring; further frames are dropped until it is drained.
+/// * `snaplen`: the maximum number of bytes captured from each frame.
+pub fn start(capacity: usize, snaplen: usize) {
+    *CAPTURE_RING.lock() = Some(CaptureRing {
+        frames: VecDeque::with_capacity(capacity),
+        capacity,
+        snaplen,
+    });
+    DROPPED_FRAMES.store(0, Ordering::SeqCst);
+    CAPTURE_ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Stops capturing frames. Frames that were already captured remain available via [`drain()`].
+pub fn stop() {
+    CAPTURE_ENABLED.store(false, Ordering::SeqCst);
+}
+
+/// Returns true if a capture is currently active.
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    CAPTURE_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns the snapshot length of the current (or last) capture.
+pub fn snaplen() -> usize {
+    CAPTURE_RING.lock().as_ref().map(|ring| ring.snaplen).unwrap_or(DEFAULT_SNAPLEN)
+}
+
+/// Returns the number of frames that were dropped because the capture ring was full.
+pub fn dropped_frames() -> usize {
+    DROPPED_FRAMES.load(Ordering::SeqCst)
+}
+
+/// Removes and returns all frames currently held in the capture ring, oldest first.
+pub fn drain() -> Vec<CapturedFrame> {
+    CAPTURE_RING.lock().as_mut().map(|ring| ring.frames.drain(..).collect()).unwrap_or_default()
+}
+
+/// Copies a frame into the capture ring, if a capture is active.
+/// 
+/// A frame may be split across multiple buffers, so it is given as a sequence of byte slices.
+/// This is meant to be invoked by NIC queues for every frame that passes through them.
+pub fn capture<'a, I: IntoIterator<Item = &'a [u8]>>(direction: Direction, queue_id: u8, pieces: I) {
+    if !is_enabled() {
+        return;
+    }
+    let tsc_ticks = tsc::tsc_ticks().into() as u64;
+
+    let mut ring_guard = CAPTURE_RING.lock();
+    let ring = match ring_guard.as_mut() {
+        Some(ring) => ring,
+        None => return,
+    };
+    if ring.frames.len() >= ring.capacity {
+        DROPPED_FRAMES.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
+    let mut data = Vec::new();
+    let mut original_length = 0;
+    for piece in pieces {
+        original_length += piece.len();
+        let remaining = ring.snaplen.saturating_sub(data.len());
+        data.extend_from_slice(&piece[..core::cmp::min(remaining, piece.len())]);
+    }
+
+    ring.frames.push_back(CapturedFrame { tsc_ticks, direction, queue_id, original_length, data });
+}
@@ -0,0 +1,156 @@
+//! A cache of PCI devices' memory-mapped BARs (Base Address Registers).
+//!
+//! Each BAR is mapped at most once, the first time a driver requests registers within it,
+//! and that mapping is then shared by all typed register structs handed out for that BAR.
+//! This prevents the same BAR from being mapped multiple times, 
+//! and also prevents two register structs from covering overlapping regions of the same BAR.
+
+use core::{
+    marker::PhantomData,
+    mem::size_of,
+    ops::{Deref, DerefMut, Range},
+};
+use alloc::{sync::Arc, vec::Vec};
+use spin::Mutex;
+use memory::{MappedPages, EntryFlags, allocate_pages_by_bytes, allocate_frames_by_bytes_at, get_kernel_mmi_ref};
+use zerocopy::FromBytes;
+use super::{PciDevice, PciLocation, PciConfigSpaceAccessMechanism};
+
+/// The flags used to map a BAR's memory region, which is uncacheable device memory.
+const BAR_MAPPING_FLAGS: EntryFlags = EntryFlags::from_bits_truncate(
+    EntryFlags::PRESENT.bits() |
+    EntryFlags::WRITABLE.bits() |
+    EntryFlags::NO_CACHE.bits() |
+    EntryFlags::NO_EXECUTE.bits()
+);
+
+/// A memory-mapped BAR of a PCI device.
+struct BarMapping {
+    location: PciLocation,
+    bar_index: usize,
+    mapped_pages: Arc<MappedPages>,
+    /// The byte ranges within this BAR that are currently covered by a `BarRegisters` struct.
+    claimed_ranges: Vec<Range<usize>>,
+}
+
+/// All BARs that have been mapped so far.
+static BAR_MAPPINGS: Mutex<Vec<BarMapping>> = Mutex::new(Vec::new());
+
+
+impl PciDevice {
+    /// Returns a struct of registers of type `T` located at the given byte `offset`
+    /// within the memory region described by the given BAR of this PCI device.
+    /// 
+    /// The BAR is mapped upon the first call for that BAR, and that mapping is reused for all later calls.
+    /// 
+    /// # Arguments
+    /// * `bar_index` must be between `0` and `5` inclusively, and must describe a memory-mapped (not I/O port) region.
+    /// * `offset`: the offset in bytes from the beginning of the BAR at which the registers are located.
+    /// 
+    /// Returns an error if the registers don't fit within the BAR, 
+    /// or if they overlap with another `BarRegisters` struct that is still in use.
+    pub fn pci_bar_registers<T: FromBytes>(&self, bar_index: usize, offset: usize) -> Result<BarRegisters<T>, &'static str> {
+        let range = offset .. offset + size_of::<T>();
+        let mut mappings = BAR_MAPPINGS.lock();
+
+        let idx = match mappings.iter().position(|m| m.location == self.location && m.bar_index == bar_index) {
+            Some(idx) => idx,
+            None => {
+                let mapped_pages = self.map_bar(bar_index)?;
+                mappings.push(BarMapping {
+                    location: self.location,
+                    bar_index,
+                    mapped_pages: Arc::new(mapped_pages),
+                    claimed_ranges: Vec::new(),
+                });
+                mappings.len() - 1
+            }
+        };
+        let mapping = &mut mappings[idx];
+
+        // Check that the registers fit within the mapped BAR.
+        mapping.mapped_pages.as_type::<T>(offset)?;
+        if mapping.claimed_ranges.iter().any(|r| r.start < range.end && range.start < r.end) {
+            error!("PCI device {}: BAR{} registers at {:#X?} overlap with registers that are already in use", 
+                self.location, bar_index, range
+            );
+            return Err("pci_bar_registers(): the requested registers overlap with registers that are already in use");
+        }
+        mapping.claimed_ranges.push(range);
+
+        Ok(BarRegisters {
+            location: self.location,
+            bar_index,
+            offset,
+            backing_pages: mapping.mapped_pages.clone(),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Maps the entire memory region described by the given BAR of this PCI device.
+    fn map_bar(&self, bar_index: usize) -> Result<MappedPages, &'static str> {
+        let bar = *self.bars.get(bar_index).ok_or("BAR index must be between 0 and 5 inclusive")?;
+        if bar & 0x1 == PciConfigSpaceAccessMechanism::IoPort as u32 {
+            return Err("pci_bar_registers(): the given BAR describes an I/O port region, not a memory-mapped region");
+        }
+        let mem_base = self.determine_mem_base(bar_index)?;
+        let mem_size_in_bytes = self.determine_mem_size(bar_index) as usize;
+
+        let pages = allocate_pages_by_bytes(mem_size_in_bytes)
+            .ok_or("pci_bar_registers(): couldn't allocate virtual pages for BAR")?;
+        let frames = allocate_frames_by_bytes_at(mem_base, mem_size_in_bytes)
+            .map_err(|_e| "pci_bar_registers(): couldn't allocate physical frames for BAR")?;
+        let kernel_mmi_ref = get_kernel_mmi_ref().ok_or("pci_bar_registers(): KERNEL_MMI was not yet initialized!")?;
+        let mapped_pages = kernel_mmi_ref.lock().page_table.map_allocated_pages_to(pages, frames, BAR_MAPPING_FLAGS)?;
+
+        debug!("PCI device {}: mapped BAR{} at {:#X} ({} bytes)", self.location, bar_index, mem_base, mem_size_in_bytes);
+        Ok(mapped_pages)
+    }
+}
+
+
+/// A struct of registers of type `T` located within a memory-mapped BAR of a PCI device,
+/// obtained from [`PciDevice::pci_bar_registers()`].
+/// 
+/// This dereferences to the registers themselves, and keeps the BAR mapped while it exists.
+/// Once dropped, the region of the BAR that it covered can be handed out again.
+pub struct BarRegisters<T: FromBytes> {
+    location: PciLocation,
+    bar_index: usize,
+    offset: usize,
+    /// We share the backing mapped pages among all the registers in the same BAR.
+    backing_pages: Arc<MappedPages>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: FromBytes> BarRegisters<T> {
+    /// Returns the offset of these registers from the beginning of their BAR.
+    pub fn offset(&self) -> usize { self.offset }
+}
+
+impl<T: FromBytes> Deref for BarRegisters<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFE: we checked that `T` fits within the backing mapped pages at this offset when this struct was created,
+        //       and the backing mapped pages cannot be dropped while this struct exists.
+        unsafe { &*((self.backing_pages.start_address().value() + self.offset) as *const T) }
+    }
+}
+
+impl<T: FromBytes> DerefMut for BarRegisters<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFE: same as above. Also, the backing mapped pages are writable, 
+        //       and no other `BarRegisters` struct covers an overlapping region.
+        unsafe { &mut *((self.backing_pages.start_address().value() + self.offset) as *mut T) }
+    }
+}
+
+impl<T: FromBytes> Drop for BarRegisters<T> {
+    fn drop(&mut self) {
+        let range = self.offset .. self.offset + size_of::<T>();
+        let mut mappings = BAR_MAPPINGS.lock();
+        if let Some(mapping) = mappings.iter_mut().find(|m| m.location == self.location && m.bar_index == self.bar_index) {
+            mapping.claimed_ranges.retain(|r| *r != range);
+        }
+    }
+}
@@ -8,6 +8,10 @@ extern crate spin;
 extern crate port_io;
 extern crate memory;
 extern crate bit_field;
+extern crate zerocopy;
+
+mod bar_mapping;
+pub use bar_mapping::BarRegisters;
 
 use core::fmt;
 use core::ops::{Deref, DerefMut};
@@ -49,9 +53,20 @@ pub const PCI_MIN_GRANT:             u16 = 0x3E;
 pub const PCI_MAX_LATENCY:           u16 = 0x3F;
 
 // PCI Capability IDs
+pub const POWER_MANAGEMENT_CAPABILITY: u16 = 0x01;
 pub const MSI_CAPABILITY:           u16 = 0x05;
 pub const MSIX_CAPABILITY:          u16 = 0x11;
 
+// The bits of the PCI command register that can be changed with `PciLocation::pci_enable()` and `pci_disable()`.
+/// Allows the device to respond to accesses to its I/O space BARs.
+pub const PCI_COMMAND_IO_SPACE:          u16 = 1 << 0;
+/// Allows the device to respond to accesses to its memory space BARs.
+pub const PCI_COMMAND_MEMORY_SPACE:      u16 = 1 << 1;
+/// Allows the device to act as a bus master, i.e., to perform DMA.
+pub const PCI_COMMAND_BUS_MASTER:        u16 = 1 << 2;
+/// Prevents the device from asserting legacy INTx interrupts.
+pub const PCI_COMMAND_INTERRUPT_DISABLE: u16 = 1 << 10;
+
 /// If a BAR's bits [2:1] equal this value, that BAR describes a 64-bit address.
 /// If not, that BAR describes a 32-bit address.
 const BAR_ADDRESS_IS_64_BIT: u32 = 2;
@@ -71,6 +86,11 @@ const CONFIG_DATA: u16 = 0xCFC;
 static PCI_CONFIG_ADDRESS_PORT: Mutex<Port<u32>> = Mutex::new(Port::new(CONFIG_ADDRESS));
 static PCI_CONFIG_DATA_PORT: Mutex<Port<u32>> = Mutex::new(Port::new(CONFIG_DATA));
 
+/// The history of all changes made to PCI devices' command registers via `PciLocation::pci_enable()` and `pci_disable()`.
+/// 
+/// This lock is also held while changing a command register, which serializes those read-modify-write operations.
+static PCI_COMMAND_CHANGES: Mutex<Vec<PciCommandChange>> = Mutex::new(Vec::new());
+
 
 
 /// Returns a list of all PCI buses in this system.
@@ -241,35 +261,146 @@ impl PciLocation {
     }
 
     /// Sets the PCI device's bit 3 in the command portion, which is apparently needed to activate DMA (??)
+    /// 
+    /// Drivers should prefer [`pci_enable()`](#method.pci_enable), which records who enabled bus mastering
+    /// and disables it again once the driver is done with the device.
     pub fn pci_set_command_bus_master_bit(&self) {
-        unsafe { 
-            PCI_CONFIG_ADDRESS_PORT.lock().write(self.pci_address(PCI_COMMAND));
-        }
-        let inval = PCI_CONFIG_DATA_PORT.lock().read(); 
-        trace!("pci_set_command_bus_master_bit: PciDevice: {}, read value: {:#x}", self, inval);
-        unsafe {
-            PCI_CONFIG_DATA_PORT.lock().write(inval | (1 << 2));
-        }
-        trace!("pci_set_command_bus_master_bit: PciDevice: {}, read value AFTER WRITE CMD: {:#x}", 
-            self,
-            PCI_CONFIG_DATA_PORT.lock().read()
-        );
+        self.pci_update_command(PCI_COMMAND_BUS_MASTER, 0, "unknown");
     }
 
     /// Sets the PCI device's command bit 10 to disable legacy interrupts
     pub fn pci_set_interrupt_disable_bit(&self) {
-        unsafe { 
-            PCI_CONFIG_ADDRESS_PORT.lock().write(self.pci_address(PCI_COMMAND));
+        self.pci_update_command(PCI_COMMAND_INTERRUPT_DISABLE, 0, "unknown");
+    }
+
+    /// Sets the given `bits` in this PCI device's command register on behalf of the given `driver`, 
+    /// e.g., `PCI_COMMAND_MEMORY_SPACE | PCI_COMMAND_BUS_MASTER`.
+    /// 
+    /// The change is recorded in the log returned by [`pci_command_changes()`].
+    /// 
+    /// Returns a guard that disables bus mastering again when dropped, e.g., when the driver
+    /// that owns the guard is unbound from this device, which prevents the device from 
+    /// performing any further DMA into memory that may have since been freed. 
+    /// If the guard should never disable bus mastering, it can be forgotten with `core::mem::forget()`.
+    pub fn pci_enable(&self, bits: u16, driver: &'static str) -> PciEnableGuard {
+        self.pci_update_command(bits, 0, driver);
+        PciEnableGuard { location: *self, bits, driver }
+    }
+
+    /// Clears the given `bits` in this PCI device's command register on behalf of the given `driver`.
+    /// 
+    /// For example, clearing `PCI_COMMAND_BUS_MASTER` fences off a misbehaving device from performing DMA. 
+    /// The change is recorded in the log returned by [`pci_command_changes()`].
+    pub fn pci_disable(&self, bits: u16, driver: &'static str) {
+        self.pci_update_command(0, bits, driver);
+    }
+
+    /// Sets the `enable` bits and clears the `disable` bits of this PCI device's command register,
+    /// and records that change in the command change log.
+    /// 
+    /// Returns the new value of the command register.
+    fn pci_update_command(&self, enable: u16, disable: u16, driver: &'static str) -> u16 {
+        let mut changes = PCI_COMMAND_CHANGES.lock();
+        let old_command = self.pci_read_16(PCI_COMMAND);
+        let new_command = (old_command | enable) & !disable;
+        // The upper 16 bits of this dword are the status register, in which writing a `1` clears a bit,
+        // so we must write zeros to avoid accidentally clearing the device's status. 
+        self.pci_write(PCI_COMMAND, new_command as u32);
+        let command = self.pci_read_16(PCI_COMMAND);
+        debug!("PCI device {}: driver {:?} changed command register from {:#X} to {:#X}", self, driver, old_command, command);
+        changes.push(PciCommandChange {
+            location: *self,
+            driver,
+            enabled: enable,
+            disabled: disable,
+            command,
+        });
+        command
+    }
+
+    /// Saves the standard configuration header of this PCI device, as well as the state of its
+    /// power management, MSI, and MSI-X capabilities (if present), 
+    /// such that it can be restored later with [`pci_restore_config()`](#method.pci_restore_config),
+    /// e.g., after a function-level reset or system suspend. 
+    pub fn pci_save_config(&self) -> PciConfigSnapshot {
+        let mut header = [0u32; PCI_CONFIG_HEADER_DWORDS];
+        for (i, dword) in header.iter_mut().enumerate() {
+            *dword = self.pci_read_32((i * 4) as u16);
+        }
+
+        let power_management = self.find_pci_capability(POWER_MANAGEMENT_CAPABILITY)
+            .map(|cap_addr| (cap_addr, self.pci_read_16(cap_addr + PM_CONTROL_STATUS_REGISTER_OFFSET)));
+
+        let msi = self.find_pci_capability(MSI_CAPABILITY).map(|cap_addr| {
+            // The size of the MSI capability depends on whether it supports 64-bit addresses and per-vector masking.
+            let control = self.pci_read_16(cap_addr + CAPABILITY_CONTROL_REGISTER_OFFSET);
+            let mut num_dwords = 3;
+            if control & MSI_CONTROL_64_BIT != 0 { num_dwords += 1; }
+            if control & MSI_CONTROL_PER_VECTOR_MASKING != 0 { num_dwords += 2; }
+            let dwords = (0..num_dwords).map(|i| self.pci_read_32(cap_addr + i * 4)).collect();
+            (cap_addr, dwords)
+        });
+
+        let msix = self.find_pci_capability(MSIX_CAPABILITY)
+            .map(|cap_addr| (cap_addr, self.pci_read_16(cap_addr + CAPABILITY_CONTROL_REGISTER_OFFSET)));
+
+        PciConfigSnapshot { location: *self, header, power_management, msi, msix }
+    }
+
+    /// Restores this PCI device's configuration to the state saved in the given `snapshot`,
+    /// which must have been obtained from this same device via [`pci_save_config()`](#method.pci_save_config).
+    /// 
+    /// The power state is restored first, then the standard header (with the command register last),
+    /// then the MSI and MSI-X capabilities. 
+    /// The change to the command register is recorded as being made by the given `driver`. 
+    /// 
+    /// Note that the MSI-X vector table resides in device memory rather than the configuration space,
+    /// so it must be restored by the device's driver itself. 
+    /// Also, if the device was in the D3hot power state, the caller must wait 10ms
+    /// after this function returns before accessing the device. 
+    pub fn pci_restore_config(&self, snapshot: &PciConfigSnapshot, driver: &'static str) -> Result<(), &'static str> {
+        if snapshot.location != *self {
+            return Err("pci_restore_config(): the given snapshot was saved from a different PCI device");
         }
-        let command = PCI_CONFIG_DATA_PORT.lock().read(); 
-        trace!("pci_set_interrupt_disable_bit: PciDevice: {}, read value: {:#x}", self, command);
 
-        const INTERRUPT_DISABLE: u32 = 1 << 10;
-        unsafe {
-            PCI_CONFIG_DATA_PORT.lock().write(command | INTERRUPT_DISABLE);
+        if let Some((cap_addr, control_status)) = snapshot.power_management {
+            // Only the power state bits are restored; the other bits are either read-only or write-one-to-clear.
+            let current = self.pci_read_16(cap_addr + PM_CONTROL_STATUS_REGISTER_OFFSET);
+            if current & PM_POWER_STATE_MASK != control_status & PM_POWER_STATE_MASK {
+                let value = (current & !PM_POWER_STATE_MASK & !PM_STATUS) | (control_status & PM_POWER_STATE_MASK);
+                self.pci_write(cap_addr + PM_CONTROL_STATUS_REGISTER_OFFSET, value as u32);
+            }
         }
-        trace!("pci_set_interrupt_disable_bit: PciDevice: {} read value AFTER WRITE CMD: {:#x}", 
-            self, PCI_CONFIG_DATA_PORT.lock().read());
+
+        // Restore the header in reverse order, skipping the read-only device/vendor IDs in dword 0
+        // and the command/status register in dword 1, which is handled below. 
+        for i in (2..PCI_CONFIG_HEADER_DWORDS).rev() {
+            let offset = (i * 4) as u16;
+            let mut saved = snapshot.header[i];
+            if offset == PCI_CACHE_LINE_SIZE {
+                // Never write to the BIST register, which could start a self-test.
+                saved &= 0x00FF_FFFF;
+            }
+            if self.pci_read_32(offset) != saved {
+                self.pci_write(offset, saved);
+            }
+        }
+        let saved_command = snapshot.header[(PCI_COMMAND / 4) as usize] as u16;
+        self.pci_update_command(saved_command, !saved_command, driver);
+
+        if let Some((cap_addr, ref dwords)) = snapshot.msi {
+            // Restore the message address/data and mask registers before the control register, which may enable MSI.
+            for (i, dword) in dwords.iter().enumerate().skip(1) {
+                self.pci_write(cap_addr + (i * 4) as u16, *dword);
+            }
+            self.pci_write(cap_addr + CAPABILITY_CONTROL_REGISTER_OFFSET, dwords[0] >> 16);
+        }
+
+        if let Some((cap_addr, control)) = snapshot.msix {
+            self.pci_write(cap_addr + CAPABILITY_CONTROL_REGISTER_OFFSET, control as u32);
+        }
+
+        Ok(())
     }
 
     /// Explores the PCI config space and returns address of requested capability, if present. 
@@ -315,6 +446,96 @@ impl PciLocation {
     }
 }
 
+/// The number of dwords in the standard PCI configuration header.
+const PCI_CONFIG_HEADER_DWORDS: usize = 16;
+/// The offset of the message control register within an MSI or MSI-X capability.
+const CAPABILITY_CONTROL_REGISTER_OFFSET: u16 = 2;
+/// If set in the MSI message control register, the MSI capability has a 64-bit message address.
+const MSI_CONTROL_64_BIT: u16 = 1 << 7;
+/// If set in the MSI message control register, the MSI capability has mask and pending registers.
+const MSI_CONTROL_PER_VECTOR_MASKING: u16 = 1 << 8;
+/// The offset of the control/status register within a power management capability.
+const PM_CONTROL_STATUS_REGISTER_OFFSET: u16 = 4;
+/// The bits of the power management control/status register that specify the power state (D0-D3hot).
+const PM_POWER_STATE_MASK: u16 = 0b11;
+/// The write-one-to-clear PME status bit of the power management control/status register.
+const PM_STATUS: u16 = 1 << 15;
+
+/// A saved copy of a PCI device's configuration, obtained from [`PciLocation::pci_save_config()`]
+/// and restored with [`PciLocation::pci_restore_config()`].
+#[derive(Clone, Debug)]
+pub struct PciConfigSnapshot {
+    location: PciLocation,
+    /// The standard configuration header.
+    header: [u32; PCI_CONFIG_HEADER_DWORDS],
+    /// The offset of the power management capability and the value of its control/status register.
+    power_management: Option<(u16, u16)>,
+    /// The offset of the MSI capability and the values of all of its dwords.
+    msi: Option<(u16, Vec<u32>)>,
+    /// The offset of the MSI-X capability and the value of its message control register.
+    msix: Option<(u16, u16)>,
+}
+
+impl PciConfigSnapshot {
+    /// Returns the location of the PCI device that this snapshot was saved from.
+    pub fn location(&self) -> PciLocation { self.location }
+}
+
+
+/// A guard returned by [`PciLocation::pci_enable()`] that disables bus mastering 
+/// for its PCI device when dropped, if bus mastering was among the bits it enabled.
+///
+/// A driver should store this guard as the *first* field of its device struct.
+/// Struct fields are dropped in declaration order, so the device is fenced off from DMA
+/// before the driver's other fields (e.g., descriptor rings and packet buffers) are freed;
+/// otherwise, the device could keep writing into memory that has since been reused.
+#[derive(Debug)]
+pub struct PciEnableGuard {
+    location: PciLocation,
+    bits: u16,
+    driver: &'static str,
+}
+
+impl PciEnableGuard {
+    /// Returns the location of the PCI device that was enabled.
+    pub fn location(&self) -> PciLocation { self.location }
+    /// Returns the command register bits that were enabled.
+    pub fn bits(&self) -> u16 { self.bits }
+    /// Returns the name of the driver that enabled the device.
+    pub fn driver(&self) -> &'static str { self.driver }
+}
+
+impl Drop for PciEnableGuard {
+    fn drop(&mut self) {
+        if self.bits & PCI_COMMAND_BUS_MASTER != 0 {
+            self.location.pci_disable(PCI_COMMAND_BUS_MASTER, self.driver);
+        }
+    }
+}
+
+
+/// A record of one change to a PCI device's command register, 
+/// made by [`PciLocation::pci_enable()`] or [`PciLocation::pci_disable()`].
+#[derive(Clone, Debug)]
+pub struct PciCommandChange {
+    /// The PCI device whose command register was changed.
+    pub location: PciLocation,
+    /// The name of the driver that requested this change.
+    pub driver: &'static str,
+    /// The command register bits that were set.
+    pub enabled: u16,
+    /// The command register bits that were cleared.
+    pub disabled: u16,
+    /// The value of the command register after this change.
+    pub command: u16,
+}
+
+/// Returns a copy of the history of changes made to PCI devices' command registers, oldest first.
+pub fn pci_command_changes() -> Vec<PciCommandChange> {
+    PCI_COMMAND_CHANGES.lock().clone()
+}
+
+
 impl fmt::Display for PciLocation {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "b{}.s{}.f{}", self.bus, self.slot, self.func)
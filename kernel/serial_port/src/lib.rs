@@ -7,6 +7,21 @@
 //! It also implements additional higher-level I/O traits for serial ports,
 //! namely [`core2::io::Read`] and [`core2::io::Write`].
 //!
+//! # Debug escape sequences
+//! Similar to Linux's "magic SysRq" over serial, the receive path of every serial port
+//! recognizes an escape sequence (by default, `~B`) or a BREAK condition on the line,
+//! after which the next received byte selects a debug action to run, e.g., dumping tasks or rebooting.
+//! Other crates can register those actions with [`register_debug_action()`];
+//! the escape sequence can be changed with [`set_escape_sequence()`].
+//! Entering `?` after the escape sequence logs the currently-registered actions.
+//!
+//! # Baud rate detection
+//! The receive path also watches for bursts of framing errors, which usually mean
+//! that the remote peer uses a different baud rate than this serial port.
+//! By default, a warning suggesting a baud rate change is logged;
+//! if [`SerialPort::start_baud_rate_probe()`] was invoked, the other standard baud rates
+//! are automatically tried one after the other until input is no longer garbled.
+//!
 //! # Notes
 //! Typically, drivers do not need to be designed in this split manner. 
 //! However, the serial port is the very earliest device to be initialized and used
@@ -30,11 +45,13 @@ use deferred_interrupt_tasks::InterruptRegistrationError;
 pub use serial_port_basic::{
     SerialPortAddress,
     SerialPortInterruptEvent,
+    UartKind,
+    STANDARD_BAUD_RATES,
     SerialPort as SerialPortBasic,
     take_serial_port as take_serial_port_basic,
 };
 
-use alloc::{boxed::Box, sync::Arc};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::{convert::TryFrom, fmt, ops::{Deref, DerefMut}};
 use irq_safety::MutexIrqSafe;
 use spin::Once;
@@ -59,6 +76,88 @@ pub fn set_connection_listener(
 static NEW_CONNECTION_NOTIFIER: Once<Sender<SerialPortAddress>> = Once::new();
 
 
+/// A debug action that can be triggered by a key following the escape sequence on a serial port.
+/// The argument is the serial port on which the action was triggered.
+pub type SerialDebugAction = fn(SerialPortAddress);
+
+/// The default escape sequence that must be received before a debug action key.
+pub const DEFAULT_ESCAPE_SEQUENCE: &'static [u8] = b"~B";
+/// The maximum length of an escape sequence.
+pub const MAX_ESCAPE_SEQUENCE_LEN: usize = 8;
+
+/// The number of framing errors within one monitoring window that indicates a baud rate mismatch.
+const FRAMING_ERROR_BURST: usize = 4;
+/// The number of received bytes (including garbled ones) over which framing errors are counted.
+const LINE_MONITOR_WINDOW: usize = 64;
+
+/// The key that logs all registered debug actions when it follows the escape sequence.
+const HELP_KEY: u8 = b'?';
+
+/// The escape sequence that precedes a debug action key.
+static ESCAPE_SEQUENCE: MutexIrqSafe<&'static [u8]> = MutexIrqSafe::new(DEFAULT_ESCAPE_SEQUENCE);
+/// The registered debug actions, each with the key that triggers it and a short description.
+static DEBUG_ACTIONS: MutexIrqSafe<Vec<(u8, &'static str, SerialDebugAction)>> = MutexIrqSafe::new(Vec::new());
+
+/// Sets the escape sequence that must be received on a serial port before a debug action key.
+///
+/// The `sequence` must be non-empty and no longer than [`MAX_ESCAPE_SEQUENCE_LEN`] bytes. 
+/// A BREAK condition on the line is always accepted in place of the escape sequence.
+pub fn set_escape_sequence(sequence: &'static [u8]) -> Result<(), &'static str> {
+    if sequence.is_empty() || sequence.len() > MAX_ESCAPE_SEQUENCE_LEN {
+        return Err("serial port escape sequence must be between 1 and MAX_ESCAPE_SEQUENCE_LEN bytes long");
+    }
+    *ESCAPE_SEQUENCE.lock() = sequence;
+    Ok(())
+}
+
+/// Registers the given debug `action` to be run when `key` is received on a serial port
+/// immediately after the escape sequence or a BREAK.
+///
+/// The `description` is logged when listing the registered actions.
+///
+/// Returns an error if an action is already registered for `key`, or if `key` is reserved.
+pub fn register_debug_action(key: u8, description: &'static str, action: SerialDebugAction) -> Result<(), &'static str> {
+    if key == HELP_KEY {
+        return Err("the '?' key is reserved for listing the registered serial debug actions");
+    }
+    let mut actions = DEBUG_ACTIONS.lock();
+    if actions.iter().any(|(k, ..)| *k == key) {
+        return Err("a serial debug action was already registered for that key");
+    }
+    actions.push((key, description, action));
+    Ok(())
+}
+
+/// Removes the debug action registered for the given `key`.
+///
+/// Returns `true` if an action was registered for `key`.
+pub fn unregister_debug_action(key: u8) -> bool {
+    let mut actions = DEBUG_ACTIONS.lock();
+    let len_before = actions.len();
+    actions.retain(|(k, ..)| *k != key);
+    actions.len() != len_before
+}
+
+/// Runs the debug action registered for the given `key`, if any.
+fn dispatch_debug_action(key: u8, serial_port_address: SerialPortAddress) {
+    if key == HELP_KEY {
+        let actions = DEBUG_ACTIONS.lock().clone();
+        let sequence = *ESCAPE_SEQUENCE.lock();
+        info!("Serial debug actions (enter after {:?} or a BREAK):", core::str::from_utf8(sequence).unwrap_or("<non-UTF-8 sequence>"));
+        for (k, description, _) in actions {
+            info!("    {:?}: {}", k as char, description);
+        }
+        return;
+    }
+    // Don't hold the lock while running the action, in case it (un)registers actions.
+    let action = DEBUG_ACTIONS.lock().iter().find(|(k, ..)| *k == key).map(|(.., a)| *a);
+    match action {
+        Some(action) => action(serial_port_address),
+        None => warn!("No serial debug action is registered for key {:?}", key as char),
+    }
+}
+
+
 // Serial ports cannot be reliably probed (discovered dynamically), thus,
 // we ensure they are exposed safely as singletons through the below static instances.
 static COM1_SERIAL_PORT: Once<Arc<MutexIrqSafe<SerialPort>>> = Once::new();
@@ -130,6 +229,37 @@ pub struct SerialPort {
     ///  * the number of bytes actually being transmitted, to be used as an index into the array,
     ///  * an array of bytes holding the actual data, up to 
     data_sender: Option<Sender<DataChunk>>,
+    /// How many bytes of the escape sequence have been received so far,
+    /// which are held back until it is known whether the full sequence follows.
+    /// If equal to the length of the escape sequence, the next byte selects a debug action.
+    escape_progress: usize,
+    /// Tracks framing errors in received data in order to detect a baud rate mismatch.
+    line_monitor: LineMonitor,
+}
+
+/// Counts framing errors in the data received on a serial port over a window of received bytes.
+#[derive(Default)]
+struct LineMonitor {
+    /// The number of bytes received in the current window.
+    bytes: usize,
+    /// The number of framing errors in the current window.
+    framing_errors: usize,
+    /// Whether other baud rates should automatically be tried upon a burst of framing errors.
+    probing: bool,
+    /// The number of baud rates tried since probing started.
+    rates_tried: usize,
+}
+
+/// A noteworthy change in the quality of the data received on a serial port.
+enum LineEvent {
+    /// Received data was garbled at the given baud rate.
+    Garbled { baud_rate: u32 },
+    /// Received data was garbled, so the baud rate was changed.
+    Switched { from: u32, to: u32 },
+    /// Probing found a baud rate at which received data is no longer garbled.
+    Settled { baud_rate: u32 },
+    /// Probing tried every standard baud rate without success and was stopped.
+    Exhausted,
 }
 impl Deref for SerialPort {
     type Target = SerialPortBasic;
@@ -150,9 +280,125 @@ impl SerialPort {
         SerialPort {
             inner: serial_port,
             data_sender: None,
+            escape_progress: 0,
+            line_monitor: LineMonitor::default(),
         }
     }
 
+    /// Starts probing for the baud rate used by the remote peer.
+    ///
+    /// Whenever a burst of framing errors is detected in received data,
+    /// the next standard baud rate is tried, until the received data is no longer garbled
+    /// or all standard baud rates have been tried.
+    pub fn start_baud_rate_probe(&mut self) {
+        self.line_monitor = LineMonitor { probing: true, ..Default::default() };
+    }
+
+    /// Stops probing for the baud rate, leaving the current baud rate in effect.
+    pub fn stop_baud_rate_probe(&mut self) {
+        self.line_monitor.probing = false;
+    }
+
+    /// Updates the framing error statistics after `bytes_received` bytes were received,
+    /// changing the baud rate if probing is active and a burst of framing errors occurred.
+    fn monitor_line(&mut self, bytes_received: usize) -> Option<LineEvent> {
+        let framing_errors = self.take_framing_errors();
+        let baud_rate = self.baud_rate();
+        let monitor = &mut self.line_monitor;
+        monitor.bytes += bytes_received;
+        monitor.framing_errors += framing_errors;
+
+        if monitor.framing_errors >= FRAMING_ERROR_BURST {
+            monitor.bytes = 0;
+            monitor.framing_errors = 0;
+            if !monitor.probing {
+                return Some(LineEvent::Garbled { baud_rate });
+            }
+            if monitor.rates_tried >= STANDARD_BAUD_RATES.len() {
+                monitor.probing = false;
+                return Some(LineEvent::Exhausted);
+            }
+            monitor.rates_tried += 1;
+            let next = STANDARD_BAUD_RATES.iter()
+                .position(|&b| b == baud_rate)
+                .map(|i| STANDARD_BAUD_RATES[(i + 1) % STANDARD_BAUD_RATES.len()])
+                .unwrap_or(STANDARD_BAUD_RATES[0]);
+            return match self.set_baud_rate(next) {
+                Ok(()) => Some(LineEvent::Switched { from: baud_rate, to: next }),
+                Err(_) => Some(LineEvent::Exhausted),
+            };
+        }
+
+        if monitor.bytes >= LINE_MONITOR_WINDOW {
+            monitor.bytes = 0;
+            monitor.framing_errors = 0;
+            if monitor.probing {
+                monitor.probing = false;
+                return Some(LineEvent::Settled { baud_rate });
+            }
+        }
+        None
+    }
+
+    /// Scans the first `len` bytes of the given `data` for the escape sequence
+    /// (or a BREAK, received just before the byte at `break_index`),
+    /// and removes each key that follows one from `data`.
+    ///
+    /// Bytes that match the beginning of the escape sequence are held back rather than passed on,
+    /// since it isn't yet known whether they're part of the sequence.
+    /// They are swallowed if the full sequence is received, or put back into `data` once a byte doesn't match.
+    /// As bytes held back from a previous chunk may be put back, `data` must have room for
+    /// up to [`MAX_ESCAPE_SEQUENCE_LEN`] more bytes than `len`.
+    ///
+    /// Returns the new length of `data` and the removed keys, which select debug actions.
+    fn scan_for_escape_sequences(&mut self, data: &mut [u8], len: usize, break_index: Option<usize>) -> (usize, Vec<u8>) {
+        let sequence = *ESCAPE_SEQUENCE.lock();
+        // The escape sequence may have changed since the held-back bytes were received.
+        self.escape_progress = self.escape_progress.min(sequence.len());
+
+        let received = data[..len].to_vec();
+        let mut keys = Vec::new();
+        let mut new_len = 0;
+        let mut put_back = |data: &mut [u8], bytes: &[u8]| {
+            data[new_len .. new_len + bytes.len()].copy_from_slice(bytes);
+            new_len += bytes.len();
+        };
+
+        for (i, &byte) in received.iter().enumerate() {
+            if break_index == Some(i) {
+                if self.escape_progress < sequence.len() {
+                    put_back(data, &sequence[..self.escape_progress]);
+                }
+                self.escape_progress = sequence.len();
+            }
+            if self.escape_progress >= sequence.len() {
+                self.escape_progress = 0;
+                keys.push(byte);
+                continue;
+            }
+            if byte == sequence[self.escape_progress] {
+                // Hold this byte back; if it completes the sequence, the held-back bytes are swallowed.
+                self.escape_progress += 1;
+                continue;
+            }
+            put_back(data, &sequence[..self.escape_progress]);
+            if byte == sequence[0] {
+                self.escape_progress = 1;
+            } else {
+                self.escape_progress = 0;
+                put_back(data, &[byte]);
+            }
+        }
+        // A BREAK received after the last byte applies to the first byte of the next chunk.
+        if break_index == Some(len) {
+            if self.escape_progress < sequence.len() {
+                put_back(data, &sequence[..self.escape_progress]);
+            }
+            self.escape_progress = sequence.len();
+        }
+        (new_len, keys)
+    }
+
     /// Register the interrupt handler for this serial port
     /// and spawn a deferrent interrupt task to handle its data receival. 
     pub fn register_interrupt_handler(
@@ -287,6 +533,8 @@ fn serial_port_receive_deferred(
     let bytes_read;
     let base_port;
     
+    let debug_keys;
+    let line_event;
     let mut input_was_ignored = false;
     let mut send_result = Ok(());
 
@@ -295,7 +543,18 @@ fn serial_port_receive_deferred(
     { 
         let mut sp = serial_port.lock();
         base_port = sp.base_port_address();
-        bytes_read = sp.in_bytes(&mut buf.data);
+        // Leave room for any bytes of a partial escape sequence held back from the previous chunk.
+        let max_read = buf.data.len() - MAX_ESCAPE_SEQUENCE_LEN;
+        let (len, break_index) = sp.in_bytes_with_break(&mut buf.data[..max_read]);
+        if len == 0 && break_index.is_none() {
+            // Ignore this interrupt, as it was caused by a `SerialPortInterruptEvent` 
+            // other than data being received, which is the only one we currently care about.
+            return Ok(());
+        }
+        line_event = sp.monitor_line(len);
+        let (len, keys) = sp.scan_for_escape_sequences(&mut buf.data, len, break_index);
+        bytes_read = len;
+        debug_keys = keys;
         if bytes_read > 0 {
             if let Some(ref sender) = sp.data_sender {
                 buf.len = bytes_read as u8;
@@ -303,10 +562,33 @@ fn serial_port_receive_deferred(
             } else {
                 input_was_ignored = true;
             }
-        } else {
-            // Ignore this interrupt, as it was caused by a `SerialPortInterruptEvent` 
-            // other than data being received, which is the only one we currently care about.
-            return Ok(());
+        }
+    }
+
+    match line_event {
+        Some(LineEvent::Garbled { baud_rate }) => warn!(
+            "Serial port {:#X}: received garbled data at {} baud; the remote peer may use a different baud rate. \
+            Consider changing the baud rate or starting a baud rate probe.",
+            base_port, baud_rate,
+        ),
+        Some(LineEvent::Switched { from, to }) => warn!(
+            "Serial port {:#X}: received garbled data at {} baud, trying {} baud.", base_port, from, to,
+        ),
+        Some(LineEvent::Settled { baud_rate }) => info!(
+            "Serial port {:#X}: received data is no longer garbled at {} baud.", base_port, baud_rate,
+        ),
+        Some(LineEvent::Exhausted) => error!(
+            "Serial port {:#X}: tried all standard baud rates, but received data is still garbled.", base_port,
+        ),
+        None => { }
+    }
+
+    if !debug_keys.is_empty() {
+        match SerialPortAddress::try_from(base_port) {
+            Ok(serial_port_address) => for key in debug_keys {
+                dispatch_debug_action(key, serial_port_address);
+            },
+            Err(_) => error!("Error: base port {:#X} was not a known serial port address.", base_port),
         }
     }
 
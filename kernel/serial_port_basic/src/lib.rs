@@ -151,7 +151,16 @@ pub struct SerialPort {
     modem_control:              Port<u8>,
     line_status:                Port<u8>,
     _modem_status:              Port<u8>,
-    _scratch:                   Port<u8>,
+    scratch:                    Port<u8>,
+    /// The detected model of this serial port's UART, which determines its FIFO depth.
+    kind:                       UartKind,
+    /// The number of bytes that can still be written to the transmit FIFO
+    /// without checking whether it has room for them.
+    tx_fifo_space:              usize,
+    /// The current baud rate (line speed) of this serial port.
+    baud_rate:                  u32,
+    /// The number of bytes received with a framing error since this was last taken.
+    framing_errors:             usize,
 }
 
 impl Drop for SerialPort {
@@ -167,7 +176,11 @@ impl Drop for SerialPort {
                     modem_control:              Port::new(0),
                     line_status:                Port::new(0),
                     _modem_status:              Port::new(0),
-                    _scratch:                   Port::new(0),
+                    scratch:                    Port::new(0),
+                    kind:                       UartKind::Uart8250,
+                    tx_fifo_space:              0,
+                    baud_rate:                  0,
+                    framing_errors:             0,
                 };
                 let dropped = core::mem::replace(self, dummy);
                 *sp_locked = TriState::Inited(dropped);
@@ -181,9 +194,10 @@ impl SerialPort {
     /// and initializes that port using standard configuration parameters. 
     /// 
     /// The configuration parameters used in this function are:
-    /// * A baud rate of 38400.
+    /// * A baud rate of 38400, i.e., [`DEFAULT_BAUD_RATE`].
     /// * "8N1" mode: data word length of 8 bits, with no parity and one stop bit.
-    /// * FIFO buffer enabled with a threshold of 14 bytes.
+    /// * FIFO buffers enabled with the deepest size supported by the UART,
+    ///   and a receive interrupt threshold close to that size; see [`UartKind`].
     /// * Interrupts enabled for receiving bytes only (not transmitting).
     ///
     /// # Arguments
@@ -194,7 +208,7 @@ impl SerialPort {
     /// Note: if you are experiencing problems with serial port behavior,
     /// try enabling the loopback test part of this function to see if that passes.
     pub fn new(base_port: u16) -> SerialPort {
-        let serial = SerialPort {
+        let mut serial = SerialPort {
             data:                       Port::new(base_port + 0),
             interrupt_enable:           Port::new(base_port + 1),
             interrupt_id_fifo_control:  Port::new(base_port + 2),
@@ -202,7 +216,11 @@ impl SerialPort {
             modem_control:              Port::new(base_port + 4),
             line_status:                Port::new(base_port + 5),
             _modem_status:              Port::new(base_port + 6),
-            _scratch:                   Port::new(base_port + 7),
+            scratch:                    Port::new(base_port + 7),
+            kind:                       UartKind::Uart8250,
+            tx_fifo_space:              0,
+            baud_rate:                  DEFAULT_BAUD_RATE,
+            framing_errors:             0,
         };
 
         // SAFE: we are just accessing this serial port's registers.
@@ -216,18 +234,27 @@ impl SerialPort {
             // To do this, we enter DLAB mode (to se the baud rate divisor),
             // the write the low byte of the divisor to the data register (DLL)
             // and the high byte to the interrupt enable register (DLH).
-            serial.data.write(0x03);
-            serial.interrupt_enable.write(0x00);
+            let divisor = (MAX_BAUD_RATE / DEFAULT_BAUD_RATE) as u16;
+            serial.data.write(divisor as u8);
+            serial.interrupt_enable.write((divisor >> 8) as u8);
 
             // Exit DLAB mode. At the same time, set the data word length to 8 bits,
             // also specifying no parity and one stop bit. This is known as "8N1" mode.
             serial.line_control.write(0x03);
 
+            // Determine which UART model this is, such that we can use its deepest FIFO. 
+            serial.kind = serial.identify();
+
             // Enable the FIFO queues (buffers in hardware) and clear both the transmit and receive queues.
-            // Also, set an interrupt threshold of 14 (0xC) bytes, which is the maximum value.
+            // Also, set the receive interrupt threshold to the highest value that still leaves some headroom.
             // Note that serial ports will fire an interrupt if there is a "small delay"
-            // between bytes, so we don't always have to wait for 14 entire bytes to arrive.
-            serial.interrupt_id_fifo_control.write(0xC7);
+            // between bytes, so we don't always have to wait for the entire threshold of bytes to arrive.
+            // The 16750's 64-byte FIFO can only be enabled while in DLAB mode.
+            if serial.kind == UartKind::Uart16750 {
+                serial.line_control.write(0x83);
+            }
+            serial.interrupt_id_fifo_control.write(serial.kind.fifo_control_value());
+            serial.line_control.write(0x03);
 
             // Mark the data terminal as ready, signal request to send
             // and enable auxilliary output #2 (used as interrupt line for CPU)
@@ -260,6 +287,113 @@ impl SerialPort {
 
     }
 
+    /// Identifies the model of this serial port's UART by probing its scratch register, 
+    /// its FIFO control bits, and (for 16650-compatible UARTs) its enhanced feature and ID registers.
+    ///
+    /// This must be invoked while interrupts are disabled on this serial port and DLAB mode is off.
+    /// The FIFOs are left in an unspecified state and must be re-configured afterwards.
+    unsafe fn identify(&self) -> UartKind {
+        // The original 8250 has no scratch register, so it cannot read back a written value.
+        self.scratch.write(0x5A);
+        if self.scratch.read() != 0x5A {
+            return UartKind::Uart8250;
+        }
+
+        // Try to enable the FIFOs, including the 64-byte FIFO bit that a 16750 only accepts in DLAB mode,
+        // and then check the FIFO status bits in the interrupt identification register.
+        self.line_control.write(0x83);
+        self.interrupt_id_fifo_control.write(0xE7);
+        self.line_control.write(0x03);
+        let iir = self.interrupt_id_fifo_control.read();
+        match iir >> 6 {
+            0b00 => return UartKind::Uart16450,
+            0b10 => return UartKind::Uart16550,
+            _ => { }
+        }
+        if iir & 0x20 == 0x20 {
+            return UartKind::Uart16750;
+        }
+
+        // 16650-compatible UARTs have an Enhanced Feature Register (EFR) in place of the FCR
+        // when the line control register is set to 0xBF, which reads back as written;
+        // on other UARTs, reading the same offset returns the (non-zero) interrupt identification register.
+        self.line_control.write(0xBF);
+        self.interrupt_id_fifo_control.write(0x00);
+        let has_efr = self.interrupt_id_fifo_control.read() == 0x00;
+        if has_efr {
+            self.interrupt_id_fifo_control.write(EFR_ENHANCED_MODE);
+        }
+        self.line_control.write(0x03);
+        if !has_efr {
+            return UartKind::Uart16550A;
+        }
+
+        // The 16C950's identification registers are read through its Indexed Control Register set,
+        // which is selected by the scratch register and accessed at the line status register's offset.
+        self.scratch.write(ICR_INDEX_ACR);
+        self.line_status.write(ACR_ID_READ_ENABLE);
+        self.scratch.write(ICR_INDEX_ID1);
+        let id1 = self.line_status.read();
+        self.scratch.write(ICR_INDEX_ACR);
+        self.line_status.write(0x00);
+
+        if id1 == OX16C950_ID1 {
+            UartKind::Uart16950
+        } else {
+            // Other 16650-compatible UARTs (e.g., the 16650 and 16850) are used like a 16550A. 
+            UartKind::Uart16550A
+        }
+    }
+
+    /// Returns the current baud rate (line speed) of this serial port.
+    pub fn baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+
+    /// Changes the baud rate (line speed) of this serial port, which may be in active use.
+    ///
+    /// This waits for all pending bytes to be transmitted at the old baud rate,
+    /// and leaves the line settings (e.g., "8N1" mode) and FIFO settings unchanged.
+    ///
+    /// The `baud_rate` must evenly divide [`MAX_BAUD_RATE`]; see [`STANDARD_BAUD_RATES`].
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), &'static str> {
+        if baud_rate == 0 || baud_rate > MAX_BAUD_RATE || MAX_BAUD_RATE % baud_rate != 0 {
+            return Err("serial port baud rate must evenly divide the maximum baud rate of 115200");
+        }
+        let divisor = (MAX_BAUD_RATE / baud_rate) as u16;
+
+        // Wait until both the transmit FIFO and the transmit shift register are empty.
+        while self.line_status.read() & 0x40 != 0x40 { }
+
+        // SAFE: we're just accessing this serial port's registers, restoring the line control afterwards.
+        unsafe {
+            let line_control = self.line_control.read();
+            let interrupt_enable = self.interrupt_enable.read();
+            self.line_control.write(line_control | 0x80);
+            self.data.write(divisor as u8);
+            self.interrupt_enable.write((divisor >> 8) as u8);
+            self.line_control.write(line_control & !0x80);
+            self.interrupt_enable.write(interrupt_enable);
+        }
+        self.baud_rate = baud_rate;
+        self.tx_fifo_space = 0;
+        Ok(())
+    }
+
+    /// Returns the number of bytes that were received with a framing error
+    /// since the last time this function was called, and resets that count.
+    ///
+    /// A burst of framing errors typically means the remote peer uses a different baud rate.
+    /// Only bytes read with [`SerialPort::in_bytes_with_break()`] are checked for framing errors.
+    pub fn take_framing_errors(&mut self) -> usize {
+        core::mem::replace(&mut self.framing_errors, 0)
+    }
+
+    /// Returns the detected model of this serial port's UART.
+    pub fn uart_kind(&self) -> UartKind {
+        self.kind
+    }
+
     /// Enable or disable interrupts on this serial port for various events.
     pub fn enable_interrupt(&mut self, event: SerialPortInterruptEvent, enable: bool) {
         let existing = self.interrupt_enable.read();
@@ -293,13 +427,18 @@ impl SerialPort {
     ///
     /// This writes the byte directly with no special cases, e.g., new lines.
     pub fn out_byte(&mut self, byte: u8) {
-        while !self.ready_to_transmit() { }
+        // Once the transmit FIFO is empty, we can fill all of it without checking the line status again.
+        if self.tx_fifo_space == 0 {
+            while !self.ready_to_transmit() { }
+            self.tx_fifo_space = self.kind.fifo_depth();
+        }
 
         // SAFE: we're just writing to the serial port, which has already been initialized.
         unsafe { 
             self.data.write(byte); 
             // E9.write(byte); // for Bochs debugging
         }
+        self.tx_fifo_space -= 1;
     }
 
     /// Write the given bytes to the serial port, blocking until data can be transmitted.
@@ -337,6 +476,39 @@ impl SerialPort {
         bytes_read
     }
 
+    /// Reads multiple bytes from the serial port into the given `buffer`, non-blocking,
+    /// in the same manner as [`SerialPort::in_bytes()`], but also detects a BREAK condition on the line.
+    ///
+    /// A BREAK is received by the UART as a NUL byte, which is *not* placed into the given `buffer`.
+    /// Other bytes received with a framing error are placed into `buffer` as usual, 
+    /// but are also counted; see [`SerialPort::take_framing_errors()`].
+    ///
+    /// Returns a tuple of the number of bytes read into the given `buffer`
+    /// and the index in `buffer` at which a BREAK was received (i.e., the index of the first byte after it), if any.
+    pub fn in_bytes_with_break(&mut self, buffer: &mut [u8]) -> (usize, Option<usize>) {
+        let mut bytes_read = 0;
+        let mut break_index = None;
+        while bytes_read < buffer.len() {
+            // The line status must be read before the data byte, since it describes the byte at the head of the FIFO.
+            let line_status = self.line_status.read();
+            if line_status & 0x01 != 0x01 {
+                break;
+            }
+            let byte = self.data.read();
+            if line_status & 0x10 == 0x10 {
+                break_index = Some(bytes_read);
+                continue;
+            }
+            // A BREAK also causes a framing error, so only count framing errors for other bytes.
+            if line_status & 0x08 == 0x08 {
+                self.framing_errors += 1;
+            }
+            buffer[bytes_read] = byte;
+            bytes_read += 1;
+        }
+        (bytes_read, break_index)
+    }
+
     /// Returns `true` if the serial port is ready to transmit a byte.
     #[inline(always)]
     pub fn ready_to_transmit(&self) -> bool {
@@ -371,3 +543,67 @@ pub enum SerialPortInterruptEvent {
     ErrorOrBreak     = 1 << 2,
     StatusChange     = 1 << 3,
 }
+
+
+/// The highest baud rate supported by a standard UART, 
+/// which is the UART's 1.8432 MHz clock divided by 16.
+pub const MAX_BAUD_RATE: u32 = 115200;
+/// The baud rate that serial ports are initialized with.
+pub const DEFAULT_BAUD_RATE: u32 = 38400;
+/// Commonly-used baud rates, from fastest to slowest.
+pub const STANDARD_BAUD_RATES: [u32; 8] = [115200, 57600, 38400, 19200, 9600, 4800, 2400, 1200];
+
+/// The Enhanced Feature Register bit that enables the enhanced functions of 16650-compatible UARTs.
+const EFR_ENHANCED_MODE: u8 = 1 << 4;
+/// The index of the 16C950's Additional Control Register in its Indexed Control Register set.
+const ICR_INDEX_ACR: u8 = 0x00;
+/// The index of the 16C950's first identification register in its Indexed Control Register set.
+const ICR_INDEX_ID1: u8 = 0x08;
+/// The Additional Control Register bit that allows the identification registers to be read.
+const ACR_ID_READ_ENABLE: u8 = 1 << 6;
+/// The value of the first identification register of an OX16C950.
+const OX16C950_ID1: u8 = 0x16;
+
+/// The models of UART that can be distinguished from each other,
+/// which mainly differ in the depth of their transmit and receive FIFOs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UartKind {
+    /// The original UART, without FIFOs or a scratch register.
+    Uart8250,
+    /// A UART without FIFOs.
+    Uart16450,
+    /// A UART whose 16-byte FIFOs are known to be buggy, so they are left disabled.
+    Uart16550,
+    /// A UART with working 16-byte FIFOs, the most common kind.
+    Uart16550A,
+    /// A UART with 64-byte FIFOs.
+    Uart16750,
+    /// A UART with 128-byte FIFOs.
+    Uart16950,
+}
+impl UartKind {
+    /// Returns the number of bytes that the transmit FIFO can hold, 
+    /// which is `1` if FIFOs are not used.
+    pub fn fifo_depth(&self) -> usize {
+        match self {
+            UartKind::Uart8250 | UartKind::Uart16450 | UartKind::Uart16550 => 1,
+            UartKind::Uart16550A => 16,
+            UartKind::Uart16750  => 64,
+            UartKind::Uart16950  => 128,
+        }
+    }
+
+    /// Returns the value to write to the FIFO control register in order to enable and clear the FIFOs,
+    /// with a receive interrupt threshold appropriate for this kind of UART.
+    fn fifo_control_value(&self) -> u8 {
+        match self {
+            UartKind::Uart8250 | UartKind::Uart16450 | UartKind::Uart16550 => 0x00,
+            // threshold of 14 bytes
+            UartKind::Uart16550A => 0xC7,
+            // 64-byte FIFOs, threshold of 56 bytes
+            UartKind::Uart16750  => 0xE7,
+            // threshold of 112 bytes, when in enhanced mode
+            UartKind::Uart16950  => 0x87,
+        }
+    }
+}
@@ -5,6 +5,8 @@
 //! * The [`sleep_until`] function delays the current task until a specific moment in the future.
 //! * The [`sleep_periodic`] function allows for tasks to be delayed for periodic intervals
 //!  of time and can be used to implement a period task.
+//! * The [`every`] function registers a callback that is periodically invoked by a shared 
+//!  housekeeping task, [`periodic_callback_task`], such that drivers needn't spawn their own periodic tasks.
 //!
 //! TODO: use regular time-keeping abstractions like Duration and Instant.
 
@@ -14,9 +16,17 @@ extern crate irq_safety;
 extern crate alloc;
 #[macro_use] extern crate lazy_static;
 extern crate scheduler;
+extern crate kernel_config;
 
-use core::sync::atomic::{Ordering, AtomicUsize};
-use alloc::collections::binary_heap::BinaryHeap;
+use core::sync::atomic::{Ordering, AtomicUsize, AtomicBool};
+use core::time::Duration;
+use alloc::{
+    boxed::Box,
+    collections::binary_heap::BinaryHeap,
+    sync::Arc,
+    vec::Vec,
+};
+use kernel_config::time::CONFIG_TIMESLICE_PERIOD_MICROSECONDS;
 use irq_safety::MutexIrqSafe;
 use task::{get_my_current_task, TaskRef};
 
@@ -134,3 +144,130 @@ pub fn sleep_periodic(last_resume_time: &AtomicUsize, period: usize) {
     let new_resume_time = last_resume_time.fetch_add(period, Ordering::SeqCst) + period;
     sleep_until(new_resume_time);
 }
+
+
+/// A callback registered with [`every`], along with when it should next be invoked.
+struct PeriodicSubscription {
+    /// The period in ticks between invocations of the callback.
+    period: usize,
+    /// The tick count at or after which the callback should next be invoked.
+    next_time: usize,
+    callback: Box<dyn FnMut() + Send>,
+    /// Set to `false` when the subscription is cancelled via its `PeriodicHandle`.
+    active: Arc<AtomicBool>,
+}
+
+/// All callbacks registered with [`every`].
+static PERIODIC_SUBSCRIPTIONS: MutexIrqSafe<Vec<PeriodicSubscription>> = MutexIrqSafe::new(Vec::new());
+
+/// The task that runs [`periodic_callback_task`], if it has been started.
+static PERIODIC_CALLBACK_TASK: MutexIrqSafe<Option<TaskRef>> = MutexIrqSafe::new(None);
+
+/// A handle to a callback registered with [`every`], which can be used to cancel it. 
+/// 
+/// Dropping this handle does *not* cancel the callback.
+pub struct PeriodicHandle {
+    active: Arc<AtomicBool>,
+}
+
+impl PeriodicHandle {
+    /// Cancels the periodic callback, such that it will not be invoked again.
+    /// 
+    /// If the callback is currently running, this does not wait for it to complete.
+    pub fn cancel(&self) {
+        self.active.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if the periodic callback has not yet been cancelled.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+}
+
+/// Converts the given `duration` into a number of ticks, rounding up.
+pub fn duration_to_ticks(duration: Duration) -> usize {
+    let period_us = CONFIG_TIMESLICE_PERIOD_MICROSECONDS as u128;
+    ((duration.as_micros() + period_us - 1) / period_us) as usize
+}
+
+/// Registers the given `callback` to be invoked once every `period`, 
+/// starting one `period` from now, e.g., for watchdogs or flushing statistics.
+/// 
+/// The `period` is rounded up to a whole number of ticks, and is at least one tick.
+/// 
+/// All periodic callbacks are invoked one after another by a single task, [`periodic_callback_task`],
+/// so a callback should complete quickly and must not block for long periods of time.
+/// A callback must not panic either, as that kills the shared task and thus stops *all* periodic callbacks
+/// until a new [`periodic_callback_task`] is spawned; the callbacks that were due at that time are discarded.
+/// If that task falls behind, missed invocations of a callback are skipped rather than run back-to-back. 
+/// Callbacks can be registered before that task has been spawned.
+pub fn every<F: FnMut() + Send + 'static>(period: Duration, callback: F) -> PeriodicHandle {
+    let period = core::cmp::max(duration_to_ticks(period), 1);
+    let active = Arc::new(AtomicBool::new(true));
+    PERIODIC_SUBSCRIPTIONS.lock().push(PeriodicSubscription {
+        period,
+        next_time: get_current_time_in_ticks() + period,
+        callback: Box::new(callback),
+        active: active.clone(),
+    });
+
+    // Wake up the periodic callback task such that it accounts for this new callback.
+    if let Some(task) = PERIODIC_CALLBACK_TASK.lock().as_ref() {
+        task.unblock();
+    }
+    PeriodicHandle { active }
+}
+
+/// The entry point of the task that invokes all callbacks registered with [`every`].
+/// 
+/// This should be spawned once during system initialization; it never returns.
+/// If it is killed, e.g., because a callback panicked, it unregisters itself
+/// such that another instance can be spawned to take over the remaining callbacks.
+pub fn periodic_callback_task(_: ()) {
+    let current_task = get_my_current_task().expect("periodic_callback_task(): couldn't get current task").clone();
+    *PERIODIC_CALLBACK_TASK.lock() = Some(current_task.clone());
+    let _res = task::set_kill_handler(Box::new(|_kill_reason| {
+        PERIODIC_CALLBACK_TASK.lock().take();
+    }));
+
+    loop {
+        let now = get_current_time_in_ticks();
+
+        // Take the due subscriptions out of the list, such that their callbacks can run 
+        // without holding its lock, which allows them to call `every()` themselves.
+        let mut due = Vec::new();
+        {
+            let mut subscriptions = PERIODIC_SUBSCRIPTIONS.lock();
+            subscriptions.retain(|sub| sub.active.load(Ordering::SeqCst));
+            let mut i = 0;
+            while i < subscriptions.len() {
+                if subscriptions[i].next_time <= now {
+                    due.push(subscriptions.swap_remove(i));
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        for sub in due.iter_mut() {
+            if sub.active.load(Ordering::SeqCst) {
+                (sub.callback)();
+            }
+            // Skip any periods that were missed.
+            let missed_periods = (now - sub.next_time) / sub.period;
+            sub.next_time += (missed_periods + 1) * sub.period;
+        }
+
+        // Sleep until the next callback is due, or indefinitely if there are none.
+        // We block this task while holding the lock such that a concurrent call to `every()` cannot be missed.
+        {
+            let mut subscriptions = PERIODIC_SUBSCRIPTIONS.lock();
+            subscriptions.extend(due.into_iter().filter(|sub| sub.active.load(Ordering::SeqCst)));
+            if let Some(resume_time) = subscriptions.iter().map(|sub| sub.next_time).min() {
+                add_to_delayed_tasklist(SleepingTaskNode { taskref: current_task.clone(), resume_time });
+            }
+            current_task.block();
+        }
+        scheduler::schedule();
+    }
+}
@@ -0,0 +1,164 @@
+//! A watchdog for long-running driver tasks, e.g., a NIC polling task or a host controller task.
+//!
+//! A task registers itself with the watchdog via [`register()`] and must then call
+//! [`WatchdogHandle::heartbeat()`] at least once per timeout period.
+//! If a heartbeat is missed, the watchdog logs a diagnostic dump about the stalled task
+//! and invokes the optional recovery function given upon registration,
+//! which can be used to reset the driver.
+//!
+//! All watchdogs are checked by a single periodic callback registered with [`sleep::every()`],
+//! so the watchdog only works once the [`sleep::periodic_callback_task`] is running.
+
+#![no_std]
+
+#[macro_use] extern crate log;
+extern crate alloc;
+extern crate spin;
+extern crate irq_safety;
+extern crate task;
+extern crate sleep;
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use irq_safety::MutexIrqSafe;
+use spin::Once;
+use task::TaskRef;
+
+/// How often all registered watchdogs are checked for missed heartbeats.
+const WATCHDOG_CHECK_PERIOD: Duration = Duration::from_millis(100);
+
+/// All currently-registered watchdogs.
+static WATCHDOGS: MutexIrqSafe<Vec<Arc<Watchdog>>> = MutexIrqSafe::new(Vec::new());
+
+/// The periodic callback that checks all watchdogs, registered upon the first call to [`register()`].
+static WATCHDOG_CHECKER: Once<sleep::PeriodicHandle> = Once::new();
+
+/// The function invoked when a watchdog's heartbeat is missed.
+type RecoveryFn = Box<dyn FnMut() + Send>;
+
+struct Watchdog {
+    /// The name of the driver worker being watched, used in diagnostic messages.
+    name: &'static str,
+    /// The task that registered this watchdog, if any.
+    task: Option<TaskRef>,
+    /// The maximum number of ticks allowed between two heartbeats.
+    timeout: usize,
+    /// The tick count of the most recent heartbeat.
+    last_heartbeat: AtomicUsize,
+    /// Whether the current missed heartbeat has already been reported,
+    /// such that each stall is only reported once.
+    expired: AtomicBool,
+    /// The total number of times this watchdog has expired.
+    expirations: AtomicUsize,
+    /// The function invoked each time this watchdog expires, if any.
+    /// It is taken out while running, such that the lock isn't held during recovery.
+    recovery: MutexIrqSafe<Option<RecoveryFn>>,
+}
+
+/// A handle to a registered watchdog, which is used to send heartbeats.
+///
+/// Dropping this handle unregisters the watchdog.
+pub struct WatchdogHandle {
+    watchdog: Arc<Watchdog>,
+}
+
+impl WatchdogHandle {
+    /// Notifies the watchdog that the watched task is still making progress.
+    pub fn heartbeat(&self) {
+        self.watchdog.last_heartbeat.store(sleep::get_current_time_in_ticks(), Ordering::Release);
+        if self.watchdog.expired.swap(false, Ordering::AcqRel) {
+            info!("watchdog {:?}: heartbeat resumed", self.watchdog.name);
+        }
+    }
+
+    /// Returns `true` if the most recent heartbeat deadline was missed
+    /// and no heartbeat has occurred since.
+    pub fn is_expired(&self) -> bool {
+        self.watchdog.expired.load(Ordering::Acquire)
+    }
+
+    /// Returns the total number of times this watchdog has expired.
+    pub fn expirations(&self) -> usize {
+        self.watchdog.expirations.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for WatchdogHandle {
+    fn drop(&mut self) {
+        WATCHDOGS.lock().retain(|w| !Arc::ptr_eq(w, &self.watchdog));
+    }
+}
+
+/// Registers a new watchdog for the current task that expires if
+/// [`WatchdogHandle::heartbeat()`] isn't invoked at least once every `timeout`.
+///
+/// The `timeout` is rounded up to a whole number of ticks,
+/// and the watchdog is checked for expiration once every 100 milliseconds.
+pub fn register(name: &'static str, timeout: Duration) -> WatchdogHandle {
+    register_inner(name, timeout, None)
+}
+
+/// Same as [`register()`], but `recovery` is also invoked each time the watchdog expires,
+/// e.g., to reset the driver whose task has stalled.
+///
+/// The `recovery` function is invoked from the shared periodic callback task,
+/// so it should complete quickly and must not block.
+pub fn register_with_recovery<F>(name: &'static str, timeout: Duration, recovery: F) -> WatchdogHandle
+    where F: FnMut() + Send + 'static
+{
+    register_inner(name, timeout, Some(Box::new(recovery)))
+}
+
+fn register_inner(name: &'static str, timeout: Duration, recovery: Option<RecoveryFn>) -> WatchdogHandle {
+    let watchdog = Arc::new(Watchdog {
+        name,
+        task: task::get_my_current_task().cloned(),
+        timeout: core::cmp::max(sleep::duration_to_ticks(timeout), 1),
+        last_heartbeat: AtomicUsize::new(sleep::get_current_time_in_ticks()),
+        expired: AtomicBool::new(false),
+        expirations: AtomicUsize::new(0),
+        recovery: MutexIrqSafe::new(recovery),
+    });
+    WATCHDOGS.lock().push(watchdog.clone());
+    WATCHDOG_CHECKER.call_once(|| sleep::every(WATCHDOG_CHECK_PERIOD, check_watchdogs));
+    WatchdogHandle { watchdog }
+}
+
+/// Checks every registered watchdog, reporting and recovering from each newly-missed heartbeat.
+fn check_watchdogs() {
+    let now = sleep::get_current_time_in_ticks();
+    // Clone the list such that recovery functions can register or drop watchdogs.
+    let watchdogs = WATCHDOGS.lock().clone();
+    for watchdog in watchdogs {
+        let elapsed = now.saturating_sub(watchdog.last_heartbeat.load(Ordering::Acquire));
+        if elapsed <= watchdog.timeout || watchdog.expired.swap(true, Ordering::AcqRel) {
+            continue;
+        }
+        let expirations = watchdog.expirations.fetch_add(1, Ordering::AcqRel) + 1;
+        dump_diagnostics(&watchdog, elapsed, expirations);
+        // Run the recovery function with the lock released, since it may take a while
+        // and interrupts are disabled while a `MutexIrqSafe` is held.
+        let recovery = watchdog.recovery.lock().take();
+        if let Some(mut recovery) = recovery {
+            warn!("watchdog {:?}: invoking recovery function", watchdog.name);
+            recovery();
+            *watchdog.recovery.lock() = Some(recovery);
+        }
+    }
+}
+
+/// Logs information about a watchdog whose heartbeat was missed.
+fn dump_diagnostics(watchdog: &Watchdog, elapsed_ticks: usize, expirations: usize) {
+    error!("watchdog {:?}: missed heartbeat, no heartbeat for {} ticks (timeout {} ticks), expiration #{}",
+        watchdog.name, elapsed_ticks, watchdog.timeout, expirations,
+    );
+    match watchdog.task.as_ref() {
+        Some(task) => error!("    task {:?} (id {}): runstate {:?}, running on core {:?}",
+            task.name, task.id, task.runstate(), task.running_on_cpu(),
+        ),
+        None => error!("    watchdog was not registered by a task"),
+    }
+}
@@ -22,6 +22,10 @@
 //! and we generally want to place them into `build/grub-isofiles/modules/`. 
 //! These directories should be passed in to this executable as command-line arguments. 
 //! 
+//! Optionally, this also emits a machine-readable description of every crate object file
+//! that was copied into the OS image, for use by IDEs and other analysis tools;
+//! see the `--output-metadata` argument. 
+//! 
 
 extern crate getopts;
 extern crate walkdir;
@@ -35,7 +39,7 @@ use std::{
     },
     env,
     fs::{self, DirEntry, File},
-    io::{self, BufRead},
+    io::{self, BufRead, Write},
     path::{Path, PathBuf},
 };
 use walkdir::WalkDir;
@@ -119,6 +123,27 @@ fn main() -> Result<(), String> {
         "additional names of crates that should be treated as application crates. Can be provided multiple times",
         "APP_CRATE_NAME"
     );
+    opts.optopt(
+        "",
+        "output-metadata",
+        "path to an output file that will describe every crate object file copied into the output objects directory
+         as a JSON array, including the crate name, its kind (kernel, application, or other), 
+         its enabled features, the rustc flags it was compiled with, and its output object file path",
+        "METADATA_FILE"
+    );
+    opts.optopt(
+        "",
+        "cargo-messages",
+        "path to a file containing the JSON messages emitted by cargo (via `--message-format=json`) when building the crates,
+         which is used to determine the features that each crate was compiled with in the `--output-metadata` file",
+        "MESSAGES_FILE"
+    );
+    opts.optopt(
+        "",
+        "rustflags",
+        "the rustc flags that all crates were compiled with, which are included in the `--output-metadata` file",
+        "FLAGS"
+    );
     opts.optflag("h", "help", "print this help menu");
 
     let matches = match opts.parse(&args[1..]) {
@@ -235,6 +260,38 @@ fn main() -> Result<(), String> {
         "",
     ).unwrap();
 
+    // Here, if requested, we describe every crate object file that we copied into the output objects directory.
+    if let Some(output_metadata_file) = matches.opt_str("output-metadata") {
+        let crate_features = match matches.opt_str("cargo-messages") {
+            Some(messages_file) => parse_cargo_messages(&messages_file)
+                .map_err(|e| format!("Error parsing cargo messages file {:?}: {:?}", messages_file, e))?,
+            None => HashMap::new(),
+        };
+        let rustflags = matches.opt_str("rustflags").unwrap_or_default();
+
+        let mut entries: Vec<CrateMetadata> = Vec::new();
+        let app_iter = app_object_files.iter().map(|(name, d)| (name, d, "application", &app_prefix));
+        let kernel_iter = kernel_objects_and_deps_files.iter().map(|(name, (d, _))| (name, d, "kernel", &kernel_prefix));
+        let other_iter = other_objects_and_deps_files.iter().map(|(name, (d, _))| (name, d, "other", &kernel_prefix));
+        for (name, dir_entry, kind, prefix) in app_iter.chain(kernel_iter).chain(other_iter) {
+            let source_path = dir_entry.path();
+            let file_name = source_path.file_name().and_then(|osstr| osstr.to_str()).unwrap().to_string();
+            let file_stem = file_name.trim_end_matches(".o");
+            let crate_name = name.split('-').next().unwrap_or(name).to_string();
+            entries.push(CrateMetadata {
+                crate_name,
+                kind,
+                features: crate_features.get(file_stem).cloned().unwrap_or_default(),
+                object_file: Path::new(&output_objects_dir).join(format!("{}{}", prefix, file_name)),
+                source_object_file: source_path,
+            });
+        }
+        // Sort the entries such that the output doesn't depend on the order of files in the input directory.
+        entries.sort_unstable_by(|a, b| a.object_file.cmp(&b.object_file));
+        write_metadata(&output_metadata_file, &entries, &rustflags)
+            .map_err(|e| format!("Error writing metadata file {:?}: {:?}", output_metadata_file, e))?;
+    }
+
     // Here, if requested, we create the sysroot directory, containing the fundamental Rust libraries 
     // that we ask cargo to build for us for Theseus's custom platform target
     // Currently this comprises core, alloc, compiler_builtins, and rustc_std_workspace_core.
@@ -461,6 +518,111 @@ fn copy_files<'p, O, P, I>(
 
 
 
+/// A description of how one crate object file in the output objects directory was built.
+struct CrateMetadata {
+    crate_name: String,
+    /// Either "kernel", "application", or "other".
+    kind: &'static str,
+    features: Vec<String>,
+    /// The path of the crate object file in the output objects directory.
+    object_file: PathBuf,
+    /// The path of the crate object file that was emitted by rustc.
+    source_object_file: PathBuf,
+}
+
+/// Parses the JSON messages that cargo emitted while building crates, one message per line,
+/// and returns a map from each built crate's file stem (e.g., "captain-<hash>") to its enabled features.
+/// 
+/// To avoid requiring a full JSON parser, this only extracts the string arrays it needs
+/// from each `compiler-artifact` message.
+fn parse_cargo_messages<P: AsRef<Path>>(messages_file: P) -> Result<HashMap<String, Vec<String>>, io::Error> {
+    let mut crate_features = HashMap::new();
+    for line in io::BufReader::new(File::open(messages_file)?).lines() {
+        let line = line?;
+        if !line.contains(r#""reason":"compiler-artifact""#) {
+            continue;
+        }
+        let features = json_string_array(&line, "features").unwrap_or_default();
+        let filenames = json_string_array(&line, "filenames").unwrap_or_default();
+        // The crate's .rlib/.rmeta file is named "lib<crate>-<hash>.<ext>", while its object file is named "<crate>-<hash>.o".
+        for filename in filenames {
+            let path = Path::new(&filename);
+            let ext = path.extension().and_then(|e| e.to_str());
+            if ext != Some(RLIB_EXTENSION) && ext != Some(RMETA_EXTENSION) {
+                continue;
+            }
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                let stem = stem.strip_prefix(DEPS_PREFIX).unwrap_or(stem);
+                crate_features.insert(stem.to_string(), features.clone());
+            }
+        }
+    }
+    Ok(crate_features)
+}
+
+/// Extracts the array of strings associated with the first occurrence of the given `key` in the given JSON `line`.
+fn json_string_array(line: &str, key: &str) -> Option<Vec<String>> {
+    let start = line.find(&format!("\"{}\":[", key))? + key.len() + 4;
+    let mut strings = Vec::new();
+    let mut chars = line[start..].chars();
+    loop {
+        match chars.next()? {
+            ']' => return Some(strings),
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => s.push(chars.next()?),
+                        c => s.push(c),
+                    }
+                }
+                strings.push(s);
+            }
+            _ => { }
+        }
+    }
+}
+
+/// Escapes the given string such that it can be used as a JSON string value.
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"'  => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Writes the given crate metadata `entries` to the given file as a JSON array, one crate per line.
+fn write_metadata<P: AsRef<Path>>(output_file: P, entries: &[CrateMetadata], rustflags: &str) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(output_file)?);
+    writeln!(file, "[")?;
+    for (i, entry) in entries.iter().enumerate() {
+        let features = entry.features.iter().map(|f| json_escape(f)).collect::<Vec<_>>().join(", ");
+        writeln!(
+            file,
+            r#"  {{"crate_name": {}, "kind": {}, "features": [{}], "rustflags": {}, "object_file": {}, "source_object_file": {}}}{}"#,
+            json_escape(&entry.crate_name),
+            json_escape(entry.kind),
+            features,
+            json_escape(rustflags),
+            json_escape(&entry.object_file.to_string_lossy()),
+            json_escape(&entry.source_object_file.to_string_lossy()),
+            if i + 1 < entries.len() { "," } else { "" },
+        )?;
+    }
+    writeln!(file, "]")?;
+    file.flush()
+}
+
+
 fn print_crates_objects(objects: &CrateObjectFiles, sorted: bool) {
     if sorted {
         let mut sorted = objects.keys().collect::<Vec<&String>>();
@@ -1,5 +1,4 @@
 use crate_metadata::{SectionType, Shndx};
-use hashbrown::HashMap;
 use mod_mgmt::serde::SerializedSection;
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -268,7 +267,7 @@ pub fn parse_nano_core_symbol_file(symbol_str: String) -> Result<ParsedCrateItem
 /// The collection of sections and symbols obtained while parsing the nano_core crate.
 #[derive(Default)]
 pub struct ParsedCrateItems {
-    pub sections: HashMap<Shndx, SerializedSection>,
+    pub sections: BTreeMap<Shndx, SerializedSection>,
     pub global_sections: BTreeSet<Shndx>,
     pub tls_sections: BTreeSet<Shndx>,
     pub data_sections: BTreeSet<Shndx>,